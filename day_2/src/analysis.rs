@@ -0,0 +1,205 @@
+// Analysis mode: splits unsafe reports into their maximal safe runs and collects statistics
+// over the whole dataset.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::policy::{Direction, SafetyPolicy, ShortReports};
+use crate::verdict::Violation;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segmentation {
+    // Level index ranges, each one a safe report on its own. A run that ends because of a
+    // direction change shares its last level with the next run, since that level is where
+    // the direction turns.
+    pub runs: Vec<Range<usize>>,
+    // Why each run ended, with indices into the whole report.
+    pub breaks: Vec<Violation>,
+}
+
+impl Segmentation {
+    pub fn longest(&self) -> Option<&Range<usize>> {
+        // max_by_key returns the last maximum, prefer the first one instead.
+        self.runs.iter().rev().max_by_key(|run| run.len())
+    }
+}
+
+fn shift(violation: Violation, offset: usize) -> Violation {
+    match violation {
+        Violation::TooShort { .. } => violation,
+        Violation::DirectionChange { index } => Violation::DirectionChange {
+            index: index + offset,
+        },
+        Violation::StepTooSmall { index, delta } => Violation::StepTooSmall {
+            index: index + offset,
+            delta,
+        },
+        Violation::StepTooLarge { index, delta } => Violation::StepTooLarge {
+            index: index + offset,
+            delta,
+        },
+    }
+}
+
+// Grows each run one level at a time for as long as the policy accepts it.
+pub fn segment(report: &[u32], policy: &SafetyPolicy) -> Segmentation {
+    // A run is a piece of a report, however short it is.
+    let policy = &SafetyPolicy {
+        short_reports: ShortReports::Safe,
+        ..policy.clone()
+    };
+
+    let mut runs: Vec<Range<usize>> = vec![];
+    let mut breaks: Vec<Violation> = vec![];
+    let mut start = 0;
+
+    while start < report.len() {
+        let mut end = start + 1;
+
+        while end < report.len() {
+            match policy.check(&report[start..=end]) {
+                Ok(()) => end += 1,
+                Err(violation) => {
+                    breaks.push(shift(violation, start));
+                    break;
+                }
+            }
+        }
+
+        runs.push(start..end);
+
+        if end == report.len() {
+            break;
+        }
+
+        // Start the next run on the last level if it can pair with the one that broke the
+        // run, otherwise right after it.
+        start = if policy.is_safe_report(&report[end - 1..=end]) {
+            end - 1
+        } else {
+            end
+        };
+    }
+
+    Segmentation { runs, breaks }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Statistics {
+    pub reports: usize,
+    pub increasing: usize,
+    pub decreasing: usize,
+    // Reports that never go up or down, with a non-strict policy in any direction.
+    pub flat: usize,
+    // Reports that go both ways, or stay flat somewhere with a strict policy.
+    pub mixed: usize,
+    // Absolute difference between adjacent levels -> number of occurrences.
+    pub steps: BTreeMap<u32, usize>,
+}
+
+impl Statistics {
+    // Reports are split by direction the same way the policy sees it, see
+    // SafetyPolicy::monotony.
+    pub fn add(&mut self, report: &[u32], policy: &SafetyPolicy) {
+        self.reports += 1;
+
+        match policy.monotony(report) {
+            Some(Direction::Increasing) => self.increasing += 1,
+            Some(Direction::Decreasing) => self.decreasing += 1,
+            Some(Direction::Any) => self.flat += 1,
+            None => self.mixed += 1,
+        }
+
+        for window in report.windows(2) {
+            *self.steps.entry(window[0].abs_diff(window[1])).or_insert(0) += 1;
+        }
+    }
+
+    fn share(&self, count: usize) -> f64 {
+        if self.reports == 0 {
+            return 0.0;
+        }
+
+        count as f64 * 100.0 / self.reports as f64
+    }
+
+    pub fn print(&self) {
+        println!("Reports: {}", self.reports);
+        println!(
+            "Increasing: {} ({:.1}%), decreasing: {} ({:.1}%), flat: {} ({:.1}%), mixed: {} ({:.1}%)",
+            self.increasing,
+            self.share(self.increasing),
+            self.decreasing,
+            self.share(self.decreasing),
+            self.flat,
+            self.share(self.flat),
+            self.mixed,
+            self.share(self.mixed)
+        );
+
+        // Bars are scaled so that the most common step size gets 50 characters.
+        let total = self.steps.values().sum::<usize>();
+        let most = self.steps.values().copied().max().unwrap_or(0);
+
+        println!("Step sizes:");
+        for (step, &count) in &self.steps {
+            let width = (count * 50).div_ceil(most.max(1));
+            println!(
+                "{step:>5} | {:<50} {count} ({:.1}%)",
+                "#".repeat(width),
+                count as f64 * 100.0 / total as f64
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn direction_change_shares_a_level() {
+        let segmentation = segment(&[1, 2, 3, 2, 1], &SafetyPolicy::default());
+
+        assert_eq!(segmentation.runs, [0..3, 2..5]);
+        assert_eq!(
+            segmentation.breaks,
+            [Violation::DirectionChange { index: 2 }]
+        );
+    }
+
+    #[test]
+    fn large_step_starts_a_new_run() {
+        let segmentation = segment(&[1, 2, 9, 10, 11], &SafetyPolicy::default());
+
+        assert_eq!(segmentation.runs, [0..2, 2..5]);
+        assert_eq!(
+            segmentation.breaks,
+            [Violation::StepTooLarge { index: 1, delta: 7 }]
+        );
+        assert_eq!(segmentation.longest(), Some(&(2..5)));
+    }
+
+    #[test]
+    fn safe_report_is_one_run() {
+        let segmentation = segment(&[7, 6, 4, 2, 1], &SafetyPolicy::default());
+
+        assert_eq!(segmentation.runs.len(), 1);
+        assert_eq!(segmentation.longest(), Some(&(0..5)));
+        assert!(segmentation.breaks.is_empty());
+    }
+
+    #[test]
+    fn longest_prefers_the_first_run() {
+        let segmentation = Segmentation {
+            runs: vec![0..3, 2..5, 5..6],
+            breaks: vec![],
+        };
+
+        assert_eq!(segmentation.longest(), Some(&(0..3)));
+        assert_eq!(
+            segment(&[1, 2, 3, 2, 1], &SafetyPolicy::default()).longest(),
+            Some(&(0..3))
+        );
+    }
+}
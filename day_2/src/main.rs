@@ -0,0 +1,511 @@
+// https://adventofcode.com/2024/day/2
+//
+// Both parts share the safety rules from the policy module. Part 1 counts the reports that
+// follow them as they are, part 2 also counts the ones the Problem Dampener can fix.
+//
+// Besides the policy flags (see policy.rs), the program accepts:
+//
+//   --threads <n>          number of threads judging reports, defaults to the number of cores
+//   --repair <n>           for every unsafe report, look for the fewest edits (at most n)
+//                          that make it safe and print the corrected report
+//   --analyze              split unsafe reports into their maximal safe runs and print
+//                          statistics over all reports
+//   --render               draw every report as a sparkline, colored when printing to a
+//                          terminal
+//   --stream               judge each line as it is read instead of reading the whole input
+//                          first, memory use stays the same for any number of reports
+
+use std::io::{BufRead, IsTerminal};
+
+mod analysis;
+mod parallel;
+mod policy;
+mod repair;
+mod sparkline;
+mod verdict;
+
+use analysis::{Segmentation, Statistics};
+use policy::{SafetyPolicy, ShortReports};
+use repair::Repair;
+use verdict::Verdict;
+
+// Calls `visit` with each report and the line number it was read from. The line and its
+// levels live in two buffers that are reused for every report, so nothing grows with the size
+// of the input. Blank lines don't hold a report, so they are left out.
+fn for_each_report(mut reader: impl BufRead, mut visit: impl FnMut(usize, &[u32])) {
+    let mut line = String::new();
+    let mut report: Vec<u32> = vec![];
+    let mut number = 0;
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+
+        number += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        parse_report(&line, &mut report);
+        visit(number, &report);
+    }
+}
+
+// Returns each report together with the line number it was read from.
+fn read_input(reader: impl BufRead) -> Vec<(usize, Vec<u32>)> {
+    let mut result: Vec<(usize, Vec<u32>)> = vec![];
+    for_each_report(reader, |number, report| {
+        result.push((number, report.to_vec()))
+    });
+    result
+}
+
+fn parse_report(line: &str, report: &mut Vec<u32>) {
+    // Split a string into slices delimited by whitespaces
+    let levels = line
+        .split_whitespace()
+        .map(|word| word.parse::<u32>().unwrap());
+
+    report.clear();
+    report.extend(levels);
+}
+
+// Removes a "--name <value>" pair from the arguments and returns the value, the remaining
+// arguments are left for the safety policy.
+fn take_value(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let index = args.iter().position(|arg| arg == name)?;
+    let value = args.get(index + 1).cloned();
+    args.drain(index..(index + 2).min(args.len()));
+
+    if value.is_none() {
+        eprintln!("error: missing value for '{name}'");
+        std::process::exit(2);
+    }
+
+    value
+}
+
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    match args.iter().position(|arg| arg == name) {
+        Some(index) => {
+            args.remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(name: &str, value: String) -> T {
+    value.parse::<T>().unwrap_or_else(|_| {
+        eprintln!("error: invalid value '{value}' for '{name}'");
+        std::process::exit(2);
+    })
+}
+
+// Everything the program does besides applying the safety policy.
+struct Options {
+    threads: usize,
+    // Maximum number of edits in repair mode.
+    repair: Option<usize>,
+    analyze: bool,
+    render: bool,
+    stream: bool,
+}
+
+impl Options {
+    fn take_from(args: &mut Vec<String>) -> Self {
+        Options {
+            threads: take_value(args, "--threads")
+                .map(|value| parse_value("--threads", value))
+                .unwrap_or_else(parallel::default_threads),
+            repair: take_value(args, "--repair").map(|value| parse_value("--repair", value)),
+            analyze: take_flag(args, "--analyze"),
+            render: take_flag(args, "--render"),
+            stream: take_flag(args, "--stream"),
+        }
+    }
+}
+
+// What happened to a single report, computed on a worker thread and printed in input order.
+enum Outcome {
+    Skipped,
+    TooShort,
+    // Repairs and segmentations are only made in their modes, and only for reports that
+    // aren't safe as they are.
+    Judged {
+        verdict: Verdict,
+        repair: Option<Option<Repair>>,
+        segmentation: Option<Segmentation>,
+    },
+}
+
+fn evaluate(report: &[u32], policy: &SafetyPolicy, options: &Options) -> Outcome {
+    if policy.is_short(report) {
+        match policy.short_reports {
+            ShortReports::Skip => return Outcome::Skipped,
+            ShortReports::Error => return Outcome::TooShort,
+            ShortReports::Safe | ShortReports::Unsafe => {}
+        }
+    }
+
+    let verdict = verdict::judge(report, policy);
+    if verdict == Verdict::Safe {
+        return Outcome::Judged {
+            verdict,
+            repair: None,
+            segmentation: None,
+        };
+    }
+
+    let repair = options
+        .repair
+        .map(|max_edits| repair::repair(report, max_edits, policy));
+    let segmentation = options.analyze.then(|| analysis::segment(report, policy));
+
+    Outcome::Judged {
+        verdict,
+        repair,
+        segmentation,
+    }
+}
+
+fn format_removals(removals: &[Vec<usize>]) -> String {
+    let sets = removals
+        .iter()
+        .map(|set| {
+            set.iter()
+                .map(|index| index.to_string())
+                .collect::<Vec<String>>()
+                .join("+")
+        })
+        .collect::<Vec<String>>();
+
+    sets.join(", ")
+}
+
+fn print_verdict(report: &[u32], verdict: &Verdict) {
+    match verdict {
+        Verdict::Safe => println!("{:?}: Safe", report),
+        Verdict::Dampened {
+            violation,
+            removals,
+        } => {
+            println!("{:?}: Unsafe, {violation}", report);
+            println!("{}", verdict::render(report, violation));
+            println!(
+                "Safe alternative by removing index {}",
+                format_removals(removals)
+            );
+        }
+        Verdict::Unsafe { violation } => {
+            println!("{:?}: Unsafe, {violation}", report);
+            println!("{}", verdict::render(report, violation));
+            println!("No safe alternatives");
+        }
+    }
+}
+
+fn print_repair(repair: &Option<Repair>, max_edits: usize) {
+    match repair {
+        Some(repair) => {
+            let edits = repair
+                .edits
+                .iter()
+                .map(|edit| edit.to_string())
+                .collect::<Vec<String>>();
+
+            println!(
+                "Repaired with {} edit(s) ({}): {:?}",
+                edits.len(),
+                edits.join(", "),
+                repair.report
+            );
+        }
+        None => println!("No repair within {max_edits} edit(s)"),
+    }
+}
+
+fn print_segmentation(report: &[u32], segmentation: &Segmentation) {
+    let runs = segmentation
+        .runs
+        .iter()
+        .map(|run| format!("{:?}", &report[run.clone()]))
+        .collect::<Vec<String>>();
+
+    println!("Safe runs: {}", runs.join(" "));
+
+    if let Some(longest) = segmentation.longest() {
+        println!(
+            "Longest run: index {}..={} ({} levels)",
+            longest.start,
+            longest.end - 1,
+            longest.len()
+        );
+    }
+
+    println!("Breaks: {}", segmentation.breaks.len());
+    for violation in &segmentation.breaks {
+        println!("  {violation}");
+    }
+}
+
+// Judges each report as it is read instead of reading the whole input first.
+fn stream(
+    reader: impl BufRead,
+    policy: &SafetyPolicy,
+    options: &Options,
+    color: bool,
+    totals: &mut Totals,
+) {
+    for_each_report(reader, |number, report| {
+        let outcome = evaluate(report, policy, options);
+        totals.record(number, report, &outcome, policy, options, color);
+    });
+}
+
+// Reads the whole input first. Reports are independent of each other, so they are judged in
+// parallel. The outcomes come back in input order and are printed from here, one report at a
+// time.
+fn judge_all(
+    reader: impl BufRead,
+    policy: &SafetyPolicy,
+    options: &Options,
+    color: bool,
+    totals: &mut Totals,
+) {
+    let input = read_input(reader);
+
+    let outcomes = parallel::map_ordered(&input, options.threads, |(_, report)| {
+        evaluate(report, policy, options)
+    });
+
+    for ((line, report), outcome) in input.iter().zip(&outcomes) {
+        totals.record(*line, report, outcome, policy, options, color);
+    }
+}
+
+// Counts for both parts and everything else printed after the last report.
+#[derive(Debug, Default, PartialEq)]
+struct Totals {
+    part_1: usize,
+    part_2: usize,
+    repaired: usize,
+    statistics: Statistics,
+}
+
+impl Totals {
+    // Prints the outcome of a report and adds it to the counts.
+    fn record(
+        &mut self,
+        line: usize,
+        report: &[u32],
+        outcome: &Outcome,
+        policy: &SafetyPolicy,
+        options: &Options,
+        color: bool,
+    ) {
+        match outcome {
+            Outcome::Skipped => println!("{:?}: Skipped", report),
+            Outcome::TooShort => {
+                eprintln!(
+                    "error: line {line}: report has {} level(s), at least {} required",
+                    report.len(),
+                    policy.min_levels
+                );
+                std::process::exit(1);
+            }
+            Outcome::Judged {
+                verdict,
+                repair,
+                segmentation,
+            } => {
+                if options.render {
+                    println!("{}", sparkline::render(report, verdict, color));
+                } else {
+                    print_verdict(report, verdict);
+                }
+                self.statistics.add(report, policy);
+
+                if let Some(segmentation) = segmentation {
+                    print_segmentation(report, segmentation);
+                }
+
+                if let (Some(repair), Some(max_edits)) = (repair, options.repair) {
+                    print_repair(repair, max_edits);
+
+                    if repair.is_some() {
+                        self.repaired += 1;
+                    }
+                }
+
+                if *verdict == Verdict::Safe {
+                    self.part_1 += 1;
+                }
+
+                if verdict.is_safe() {
+                    self.part_2 += 1;
+                }
+            }
+        }
+    }
+
+    fn print(&self, policy: &SafetyPolicy, options: &Options) {
+        println!("Policy: {policy}");
+        println!("Part 1 safe count: {}", self.part_1);
+        println!("Part 2 safe count: {}", self.part_2);
+
+        if options.repair.is_some() {
+            println!("Repaired unsafe reports: {}", self.repaired);
+        }
+
+        if options.analyze {
+            self.statistics.print();
+        }
+    }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1).collect::<Vec<String>>();
+    let options = Options::take_from(&mut args);
+
+    let policy = match SafetyPolicy::from_args(&args) {
+        Ok(policy) => policy,
+        Err(err) => {
+            eprintln!("error: {err}");
+            std::process::exit(2);
+        }
+    };
+
+    // Escape codes only make sense in a terminal, not in a file or a pipe.
+    let color = std::io::stdout().is_terminal();
+    let mut totals = Totals::default();
+
+    let file = std::fs::File::open("input.txt").unwrap();
+    let reader = std::io::BufReader::new(file);
+
+    if options.stream {
+        stream(reader, &policy, &options, color, &mut totals);
+    } else {
+        judge_all(reader, &policy, &options, color, &mut totals);
+    }
+
+    totals.print(&policy, &options);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> Options {
+        Options {
+            threads: 1,
+            repair: None,
+            analyze: false,
+            render: false,
+            stream: false,
+        }
+    }
+
+    fn short_reports(mode: ShortReports) -> SafetyPolicy {
+        SafetyPolicy {
+            short_reports: mode,
+            ..SafetyPolicy::default()
+        }
+    }
+
+    #[test]
+    fn blank_lines_are_skipped() {
+        let input = "7 6 4 2 1\n\n  \n1 2 7 8 9\n\t\n1 3 6 7 9\n";
+
+        assert_eq!(
+            read_input(input.as_bytes()),
+            [
+                (1, vec![7, 6, 4, 2, 1]),
+                (4, vec![1, 2, 7, 8, 9]),
+                (6, vec![1, 3, 6, 7, 9]),
+            ]
+        );
+    }
+
+    #[test]
+    fn streaming_gives_the_same_totals() {
+        let input = "7 6 4 2 1\n1 2 7 8 9\n\n9 7 6 2 1\n1 3 2 4 5\n8 6 4 4 1\n  \n1 3 6 7 9\n5\n";
+        let policy = SafetyPolicy::default();
+        let options = Options {
+            threads: 3,
+            repair: Some(2),
+            analyze: true,
+            ..options()
+        };
+
+        let mut streamed = Totals::default();
+        stream(input.as_bytes(), &policy, &options, false, &mut streamed);
+
+        let mut judged = Totals::default();
+        judge_all(input.as_bytes(), &policy, &options, false, &mut judged);
+
+        assert_eq!(streamed, judged);
+        assert_eq!((streamed.part_1, streamed.part_2), (3, 5));
+        assert_eq!(streamed.statistics.reports, 7);
+    }
+
+    #[test]
+    fn short_reports_skip() {
+        let outcome = evaluate(&[5], &short_reports(ShortReports::Skip), &options());
+        assert!(matches!(outcome, Outcome::Skipped));
+    }
+
+    #[test]
+    fn short_reports_safe() {
+        let outcome = evaluate(&[5], &short_reports(ShortReports::Safe), &options());
+        assert!(matches!(
+            outcome,
+            Outcome::Judged {
+                verdict: Verdict::Safe,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn short_reports_unsafe() {
+        let outcome = evaluate(&[5], &short_reports(ShortReports::Unsafe), &options());
+        assert!(matches!(
+            outcome,
+            Outcome::Judged {
+                verdict: Verdict::Unsafe {
+                    violation: verdict::Violation::TooShort { len: 1, min: 2 }
+                },
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn short_reports_error() {
+        let outcome = evaluate(&[5], &short_reports(ShortReports::Error), &options());
+        assert!(matches!(outcome, Outcome::TooShort));
+    }
+
+    // Only reports below min-levels are short, in every mode.
+    #[test]
+    fn long_reports_are_judged() {
+        for mode in [
+            ShortReports::Skip,
+            ShortReports::Safe,
+            ShortReports::Unsafe,
+            ShortReports::Error,
+        ] {
+            let outcome = evaluate(&[1, 3, 2, 4, 5], &short_reports(mode), &options());
+            assert!(matches!(
+                outcome,
+                Outcome::Judged {
+                    verdict: Verdict::Dampened { .. },
+                    ..
+                }
+            ));
+        }
+    }
+}
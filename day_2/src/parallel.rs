@@ -0,0 +1,108 @@
+// Runs a function over every item of a slice on several threads and returns the results in
+// the same order as the items, so the output doesn't depend on how the work was scheduled.
+
+pub fn default_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(|count| count.get())
+        .unwrap_or(1)
+}
+
+pub fn map_ordered<T, R, F>(items: &[T], threads: usize, function: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    if threads <= 1 || items.len() <= 1 {
+        return items.iter().map(function).collect();
+    }
+
+    // One contiguous chunk per thread. Joining the threads in the order they were spawned
+    // puts the chunks back together in input order.
+    let chunk_size = items.len().div_ceil(threads);
+    let function = &function;
+
+    std::thread::scope(|scope| {
+        let handles = items
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(function).collect::<Vec<R>>()))
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(value: &u64) -> u64 {
+        value * value
+    }
+
+    #[test]
+    fn same_order_as_one_thread() {
+        let items = (0..103).collect::<Vec<u64>>();
+        let expected = map_ordered(&items, 1, square);
+
+        for threads in [2, 3, 7, 10, 64, 200] {
+            assert_eq!(map_ordered(&items, threads, square), expected, "{threads}");
+        }
+    }
+
+    #[test]
+    fn more_threads_than_items() {
+        let items = [4u64, 1, 3];
+
+        assert_eq!(
+            map_ordered(&items, 8, square),
+            map_ordered(&items, 1, square)
+        );
+        assert_eq!(map_ordered(&items, 8, square), [16, 1, 9]);
+    }
+
+    #[test]
+    fn same_verdicts_as_one_thread() {
+        use crate::policy::SafetyPolicy;
+        use crate::verdict::{self, Verdict};
+
+        let policy = SafetyPolicy::default();
+        let reports = (0..500u32)
+            .map(|seed| {
+                // Steps of 0 to 4, with a spike in every seventh report.
+                let spike = if seed % 7 == 0 { 6 } else { 0 };
+                (0..5)
+                    .map(|level| {
+                        seed % 30 + level * (seed % 5) + if level == 2 { spike } else { 0 }
+                    })
+                    .collect()
+            })
+            .collect::<Vec<Vec<u32>>>();
+        let judge = |report: &Vec<u32>| verdict::judge(report, &policy);
+
+        let expected = map_ordered(&reports, 1, judge);
+        for threads in [3, 7, 1000] {
+            let verdicts = map_ordered(&reports, threads, judge);
+
+            assert_eq!(verdicts, expected);
+            assert_eq!(
+                verdicts.iter().filter(|verdict| verdict.is_safe()).count(),
+                expected.iter().filter(|verdict| verdict.is_safe()).count()
+            );
+        }
+
+        // Not every report is judged the same way.
+        assert!(expected.contains(&Verdict::Safe));
+        assert!(!expected.iter().all(|verdict| *verdict == Verdict::Safe));
+    }
+
+    #[test]
+    fn empty_input() {
+        assert_eq!(map_ordered(&[], 1, square), Vec::<u64>::new());
+        assert_eq!(map_ordered(&[], 4, square), Vec::<u64>::new());
+        assert_eq!(map_ordered(&[], 0, square), Vec::<u64>::new());
+    }
+}
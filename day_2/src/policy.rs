@@ -0,0 +1,504 @@
+// The rules that decide whether a report is safe. The defaults are the ones from the puzzle:
+// levels strictly increasing or strictly decreasing, adjacent levels differing by 1 to 3 and
+// at most one level removed by the Problem Dampener.
+//
+// A policy can be loaded from a config file with "key = value" lines and/or overridden by
+// command line flags:
+//
+//   --config <path>        read settings from a file first
+//   --min-step <n>         smallest allowed difference between adjacent levels
+//   --max-step <n>         largest allowed difference between adjacent levels
+//   --direction <dir>      "any", "increasing" or "decreasing"
+//   --strict / --non-strict
+//                          whether equal adjacent levels break monotony
+//   --max-removals <n>     how many levels the Problem Dampener may remove
+//   --min-levels <n>       reports with fewer levels are "short"
+//   --short-reports <mode> what to do with short reports: "skip" them, count them as "safe"
+//                          or "unsafe", or stop with an "error"
+
+use std::fmt;
+
+use crate::verdict::Violation;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Any,
+    Increasing,
+    Decreasing,
+}
+
+impl std::str::FromStr for Direction {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "any" => Ok(Direction::Any),
+            "increasing" => Ok(Direction::Increasing),
+            "decreasing" => Ok(Direction::Decreasing),
+            _ => Err(format!(
+                "invalid direction '{str}', expected any, increasing or decreasing"
+            )),
+        }
+    }
+}
+
+// A report with fewer than two levels has no steps, so the rules can't say anything about it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShortReports {
+    Skip,
+    Safe,
+    Unsafe,
+    Error,
+}
+
+impl std::str::FromStr for ShortReports {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "skip" => Ok(ShortReports::Skip),
+            "safe" => Ok(ShortReports::Safe),
+            "unsafe" => Ok(ShortReports::Unsafe),
+            "error" => Ok(ShortReports::Error),
+            _ => Err(format!(
+                "invalid short report mode '{str}', expected skip, safe, unsafe or error"
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SafetyPolicy {
+    pub min_step: u32,
+    pub max_step: u32,
+    // When true, two equal adjacent levels are neither increasing nor decreasing.
+    pub strict: bool,
+    pub direction: Direction,
+    pub max_removals: usize,
+    // Reports with fewer levels are handled according to `short_reports`.
+    pub min_levels: usize,
+    pub short_reports: ShortReports,
+}
+
+impl Default for SafetyPolicy {
+    fn default() -> Self {
+        SafetyPolicy {
+            min_step: 1,
+            max_step: 3,
+            strict: true,
+            direction: Direction::Any,
+            max_removals: 1,
+            min_levels: 2,
+            short_reports: ShortReports::Safe,
+        }
+    }
+}
+
+impl fmt::Display for SafetyPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "step {}..={}, {} {:?}, up to {} removal(s), less than {} level(s): {:?}",
+            self.min_step,
+            self.max_step,
+            if self.strict { "strict" } else { "non-strict" },
+            self.direction,
+            self.max_removals,
+            self.min_levels,
+            self.short_reports
+        )
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse::<T>()
+        .map_err(|_| format!("invalid value '{value}' for '{key}'"))
+}
+
+impl SafetyPolicy {
+    // Applies a single setting. Keys are shared between the config file and the command line,
+    // "min-step" in the file is the same as "--min-step" on the command line.
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "min-step" => self.min_step = parse_number(key, value)?,
+            "max-step" => self.max_step = parse_number(key, value)?,
+            "strict" => self.strict = parse_number(key, value)?,
+            "direction" => self.direction = value.parse()?,
+            "max-removals" => self.max_removals = parse_number(key, value)?,
+            "min-levels" => self.min_levels = parse_number(key, value)?,
+            "short-reports" => self.short_reports = value.parse()?,
+            _ => return Err(format!("unknown setting '{key}'")),
+        }
+
+        Ok(())
+    }
+
+    fn validate(self) -> Result<Self, String> {
+        if self.min_step > self.max_step {
+            return Err(format!(
+                "min-step ({}) is greater than max-step ({})",
+                self.min_step, self.max_step
+            ));
+        }
+
+        Ok(self)
+    }
+
+    // Reads "key = value" lines. Empty lines and lines starting with '#' are ignored.
+    pub fn load_config(&mut self, path: &str) -> Result<(), String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| format!("cannot read config '{path}': {err}"))?;
+
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("{path}:{}: expected 'key = value'", index + 1))?;
+
+            self.set(key.trim(), value.trim())
+                .map_err(|err| format!("{path}:{}: {err}", index + 1))?;
+        }
+
+        Ok(())
+    }
+
+    // Builds the policy from the command line arguments (without the program name).
+    // A config file is applied first, so that flags can override its values regardless
+    // of the order in which they were given.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut policy = SafetyPolicy::default();
+
+        if let Some(index) = args.iter().position(|arg| arg == "--config") {
+            let path = args.get(index + 1).ok_or("missing value for '--config'")?;
+            policy.load_config(path)?;
+        }
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--strict" => policy.strict = true,
+                "--non-strict" => policy.strict = false,
+                "--config" => {
+                    iter.next();
+                }
+                flag => {
+                    let key = flag
+                        .strip_prefix("--")
+                        .ok_or_else(|| format!("unexpected argument '{flag}'"))?;
+                    let value = iter
+                        .next()
+                        .ok_or_else(|| format!("missing value for '{flag}'"))?;
+                    policy.set(key, value)?;
+                }
+            }
+        }
+
+        policy.validate()
+    }
+
+    fn is_step_allowed(&self, step: u32) -> bool {
+        (self.min_step..=self.max_step).contains(&step)
+    }
+
+    // The direction of the step between two adjacent levels, None when they are equal.
+    fn step_direction(lhs: u32, rhs: u32) -> Option<Direction> {
+        match lhs.cmp(&rhs) {
+            std::cmp::Ordering::Less => Some(Direction::Increasing),
+            std::cmp::Ordering::Greater => Some(Direction::Decreasing),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+
+    // The direction the levels keep by the same rules as check, whatever the step sizes.
+    // None if they go both ways, or stay flat somewhere when strict. Levels that never go up
+    // or down keep the direction the policy asks for, Direction::Any by default.
+    pub fn monotony(&self, report: &[u32]) -> Option<Direction> {
+        let mut result = self.direction;
+
+        for window in report.windows(2) {
+            match (Self::step_direction(window[0], window[1]), result) {
+                (None, _) if self.strict => return None,
+                (Some(direction), Direction::Any) => result = direction,
+                (Some(direction), expected) if direction != expected => return None,
+                _ => {}
+            }
+        }
+
+        Some(result)
+    }
+
+    pub fn is_short(&self, report: &[u32]) -> bool {
+        report.len() < self.min_levels
+    }
+
+    // Walks the report once and returns the first rule it breaks.
+    // With Direction::Any the direction is given by the first step that isn't flat.
+    //
+    // Short reports are only safe in the "safe" mode. Skipping them or stopping with an error
+    // is up to the caller, this only sees them as alternatives left after removing levels,
+    // which can't be valid reports in those modes.
+    pub fn check(&self, report: &[u32]) -> Result<(), Violation> {
+        if self.is_short(report) {
+            return match self.short_reports {
+                ShortReports::Safe => Ok(()),
+                _ => Err(Violation::TooShort {
+                    len: report.len(),
+                    min: self.min_levels,
+                }),
+            };
+        }
+
+        let mut expected = match self.direction {
+            Direction::Any => None,
+            direction => Some(direction),
+        };
+
+        for (index, window) in report.windows(2).enumerate() {
+            let delta = window[1] as i64 - window[0] as i64;
+            let step = window[0].abs_diff(window[1]);
+            let direction = Self::step_direction(window[0], window[1]);
+
+            match (direction, expected) {
+                // A flat step only breaks strict monotony. If it is also too small,
+                // that's the more useful thing to say about it.
+                (None, _) if self.strict && self.is_step_allowed(step) => {
+                    return Err(Violation::DirectionChange { index });
+                }
+                (Some(direction), Some(expected)) if direction != expected => {
+                    return Err(Violation::DirectionChange { index });
+                }
+                (Some(direction), None) => expected = Some(direction),
+                _ => {}
+            }
+
+            if step < self.min_step {
+                return Err(Violation::StepTooSmall { index, delta });
+            }
+
+            if step > self.max_step {
+                return Err(Violation::StepTooLarge { index, delta });
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn is_safe_report(&self, report: &[u32]) -> bool {
+        self.check(report).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The reports from the puzzle description.
+    const EXAMPLE: [&[u32]; 6] = [
+        &[7, 6, 4, 2, 1],
+        &[1, 2, 7, 8, 9],
+        &[9, 7, 6, 2, 1],
+        &[1, 3, 2, 4, 5],
+        &[8, 6, 4, 4, 1],
+        &[1, 3, 6, 7, 9],
+    ];
+
+    #[test]
+    fn example_reports() {
+        let policy = SafetyPolicy::default();
+        let safe = EXAMPLE
+            .iter()
+            .map(|report| policy.is_safe_report(report))
+            .collect::<Vec<bool>>();
+
+        assert_eq!(safe, [true, false, false, false, false, true]);
+    }
+
+    #[test]
+    fn example_violations() {
+        let policy = SafetyPolicy::default();
+        let violations = EXAMPLE
+            .iter()
+            .map(|report| policy.check(report).err())
+            .collect::<Vec<Option<Violation>>>();
+
+        assert_eq!(
+            violations,
+            [
+                None,
+                Some(Violation::StepTooLarge { index: 1, delta: 5 }),
+                Some(Violation::StepTooLarge {
+                    index: 2,
+                    delta: -4
+                }),
+                Some(Violation::DirectionChange { index: 1 }),
+                // Flat and too small, the step size is reported.
+                Some(Violation::StepTooSmall { index: 2, delta: 0 }),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn direction() {
+        let increasing = SafetyPolicy {
+            direction: Direction::Increasing,
+            ..SafetyPolicy::default()
+        };
+        let decreasing = SafetyPolicy {
+            direction: Direction::Decreasing,
+            ..SafetyPolicy::default()
+        };
+
+        assert!(increasing.is_safe_report(&[1, 3, 6, 7, 9]));
+        assert_eq!(
+            increasing.check(&[7, 6, 4, 2, 1]),
+            Err(Violation::DirectionChange { index: 0 })
+        );
+        assert!(decreasing.is_safe_report(&[7, 6, 4, 2, 1]));
+        assert_eq!(
+            decreasing.check(&[1, 3, 6, 7, 9]),
+            Err(Violation::DirectionChange { index: 0 })
+        );
+    }
+
+    #[test]
+    fn strict() {
+        let strict = SafetyPolicy {
+            min_step: 0,
+            ..SafetyPolicy::default()
+        };
+        let non_strict = SafetyPolicy {
+            strict: false,
+            ..strict.clone()
+        };
+
+        assert_eq!(
+            strict.check(&[1, 1, 2]),
+            Err(Violation::DirectionChange { index: 0 })
+        );
+        assert!(non_strict.is_safe_report(&[1, 1, 2]));
+        assert!(non_strict.is_safe_report(&[3, 3, 2, 2]));
+        // A flat step doesn't set the direction.
+        assert_eq!(
+            non_strict.check(&[2, 2, 3, 1]),
+            Err(Violation::DirectionChange { index: 2 })
+        );
+    }
+
+    #[test]
+    fn monotony() {
+        let strict = SafetyPolicy::default();
+        let non_strict = SafetyPolicy {
+            strict: false,
+            ..SafetyPolicy::default()
+        };
+        let decreasing = SafetyPolicy {
+            direction: Direction::Decreasing,
+            ..non_strict.clone()
+        };
+
+        // Step sizes don't matter.
+        assert_eq!(strict.monotony(&[1, 20, 21]), Some(Direction::Increasing));
+        assert_eq!(strict.monotony(&[8, 6, 4, 4, 1]), None);
+        assert_eq!(
+            non_strict.monotony(&[8, 6, 4, 4, 1]),
+            Some(Direction::Decreasing)
+        );
+        assert_eq!(non_strict.monotony(&[1, 3, 2]), None);
+        assert_eq!(non_strict.monotony(&[4, 4]), Some(Direction::Any));
+        assert_eq!(decreasing.monotony(&[4, 4]), Some(Direction::Decreasing));
+        assert_eq!(decreasing.monotony(&[1, 3, 6, 7, 9]), None);
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    // Writes a config file that is only used by one test.
+    fn config(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!("day_2_{}_{name}.conf", std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn flags_override_config() {
+        let path = config(
+            "override",
+            "# tolerances\nmin-step = 2\nmax-step = 4\n\nstrict = false\n",
+        );
+
+        for order in [
+            args(&["--max-step", "6", "--strict", "--config", &path]),
+            args(&["--config", &path, "--max-step", "6", "--strict"]),
+        ] {
+            assert_eq!(
+                SafetyPolicy::from_args(&order),
+                Ok(SafetyPolicy {
+                    min_step: 2,
+                    max_step: 6,
+                    strict: true,
+                    ..SafetyPolicy::default()
+                })
+            );
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn unknown_settings() {
+        let path = config("unknown", "min-step = 1\ntolerance = 3\n");
+
+        assert_eq!(
+            SafetyPolicy::from_args(&args(&["--config", &path])),
+            Err(format!("{path}:2: unknown setting 'tolerance'"))
+        );
+        assert_eq!(
+            SafetyPolicy::from_args(&args(&["--tolerance", "3"])),
+            Err("unknown setting 'tolerance'".to_string())
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn invalid_step_range() {
+        assert_eq!(
+            SafetyPolicy::from_args(&args(&["--min-step", "4"])),
+            Err("min-step (4) is greater than max-step (3)".to_string())
+        );
+        assert_eq!(
+            SafetyPolicy::from_args(&args(&["--min-step", "4", "--max-step", "4"]))
+                .map(|policy| policy.max_step),
+            Ok(4)
+        );
+    }
+
+    #[test]
+    fn step_bounds() {
+        let policy = SafetyPolicy {
+            min_step: 2,
+            max_step: 4,
+            ..SafetyPolicy::default()
+        };
+
+        assert!(policy.is_safe_report(&[1, 3, 7]));
+        assert_eq!(
+            policy.check(&[1, 3, 4]),
+            Err(Violation::StepTooSmall { index: 1, delta: 1 })
+        );
+        assert_eq!(
+            policy.check(&[9, 4]),
+            Err(Violation::StepTooLarge {
+                index: 0,
+                delta: -5
+            })
+        );
+    }
+}
@@ -0,0 +1,360 @@
+// Repair mode: instead of only removing a level like the Problem Dampener, look for the
+// smallest number of edits that turns a report into a safe one. An edit removes a level,
+// replaces a level with another value, or inserts a value halfway between two neighbours.
+//
+// The search goes breadth first, so the first safe report found needs the fewest edits.
+// The policy's is_safe_report is the only acceptance check.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::policy::SafetyPolicy;
+
+// Indices refer to the report as it is when the edit is applied, edits are applied in order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edit {
+    Remove { index: usize, value: u32 },
+    Replace { index: usize, from: u32, to: u32 },
+    Insert { index: usize, value: u32 },
+}
+
+impl Edit {
+    fn apply(&self, report: &[u32]) -> Vec<u32> {
+        let mut result = report.to_vec();
+
+        match *self {
+            Edit::Remove { index, .. } => {
+                result.remove(index);
+            }
+            Edit::Replace { index, to, .. } => result[index] = to,
+            Edit::Insert { index, value } => result.insert(index, value),
+        }
+
+        result
+    }
+}
+
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Edit::Remove { index, value } => write!(f, "remove {value} at index {index}"),
+            Edit::Replace { index, from, to } => {
+                write!(f, "replace {from} with {to} at index {index}")
+            }
+            Edit::Insert { index, value } => write!(f, "insert {value} at index {index}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Repair {
+    pub edits: Vec<Edit>,
+    pub report: Vec<u32>,
+}
+
+// The levels that can be one step away from `level`, going up or down, as an inclusive range.
+fn within_step(level: u32, up: bool, policy: &SafetyPolicy) -> (i64, i64) {
+    // A flat step never helps a strict policy.
+    let min_step = if policy.strict {
+        policy.min_step.max(1)
+    } else {
+        policy.min_step
+    } as i64;
+    let max_step = policy.max_step as i64;
+
+    if up {
+        (level as i64 + min_step, level as i64 + max_step)
+    } else {
+        (level as i64 - max_step, level as i64 - min_step)
+    }
+}
+
+// Values for the level between `previous` and `next` worth trying. While more edits can
+// follow, a neighbour may still change, so every value one step away from either of them is
+// tried. The last edit has to fit both neighbours as they are, and any value that does gives
+// the same verdict, so only one is tried per direction.
+fn replacements(
+    previous: Option<u32>,
+    next: Option<u32>,
+    last: bool,
+    policy: &SafetyPolicy,
+) -> Vec<u32> {
+    let mut result: Vec<i64> = vec![];
+
+    for rising in [true, false] {
+        let after = previous.map(|previous| within_step(previous, rising, policy));
+        let before = next.map(|next| within_step(next, !rising, policy));
+
+        if last {
+            let (low, high) = [after, before].into_iter().flatten().fold(
+                (i64::MIN, i64::MAX),
+                |(low, high), (range_low, range_high)| (low.max(range_low), high.min(range_high)),
+            );
+
+            if low <= high {
+                result.push(low);
+            }
+        } else {
+            for (low, high) in [after, before].into_iter().flatten() {
+                result.extend(low..=high);
+            }
+        }
+    }
+
+    result.sort();
+    result.dedup();
+
+    result
+        .into_iter()
+        .filter_map(|value| u32::try_from(value).ok())
+        .collect()
+}
+
+// Every single edit worth trying on the report. Replacement values are picked from the steps
+// allowed around the neighbours of the replaced level (see replacements), `last` is set when
+// no edit can follow.
+fn candidate_edits(report: &[u32], last: bool, policy: &SafetyPolicy) -> Vec<Edit> {
+    let mut result: Vec<Edit> = vec![];
+
+    for (index, &value) in report.iter().enumerate() {
+        result.push(Edit::Remove { index, value });
+    }
+
+    for (index, &from) in report.iter().enumerate() {
+        let previous = index.checked_sub(1).map(|index| report[index]);
+        let next = report.get(index + 1).copied();
+
+        for to in replacements(previous, next, last, policy)
+            .into_iter()
+            .filter(|&to| to != from)
+        {
+            result.push(Edit::Replace { index, from, to });
+        }
+    }
+
+    for (index, window) in report.windows(2).enumerate() {
+        let value = window[0].midpoint(window[1]);
+        result.push(Edit::Insert {
+            index: index + 1,
+            value,
+        });
+    }
+
+    result
+}
+
+pub fn repair(report: &[u32], max_edits: usize, policy: &SafetyPolicy) -> Option<Repair> {
+    let mut frontier = vec![Repair {
+        edits: vec![],
+        report: report.to_vec(),
+    }];
+
+    if policy.is_safe_report(report) {
+        return frontier.pop();
+    }
+
+    // Different edit sequences often lead to the same report, only the first one is kept.
+    let mut seen: HashSet<Vec<u32>> = HashSet::new();
+    seen.insert(report.to_vec());
+
+    for depth in 1..=max_edits {
+        let mut next: Vec<Repair> = vec![];
+
+        // Every report with fewer edits has been checked already, so the first safe one is
+        // returned right away. Nothing is kept after the last round.
+        for candidate in &frontier {
+            for edit in candidate_edits(&candidate.report, depth == max_edits, policy) {
+                let report = edit.apply(&candidate.report);
+                if seen.contains(&report) {
+                    continue;
+                }
+
+                let mut edits = candidate.edits.clone();
+                edits.push(edit);
+
+                if policy.is_safe_report(&report) {
+                    return Some(Repair { edits, report });
+                }
+
+                if depth < max_edits {
+                    seen.insert(report.clone());
+                    next.push(Repair { edits, report });
+                }
+            }
+        }
+
+        frontier = next;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fewest_edits() {
+        let policy = SafetyPolicy::default();
+
+        assert_eq!(
+            repair(&[7, 6, 4, 2, 1], 2, &policy).map(|repair| repair.edits),
+            Some(vec![])
+        );
+        assert_eq!(
+            repair(&[9, 7, 6, 2, 1], 2, &policy),
+            Some(Repair {
+                edits: vec![Edit::Replace {
+                    index: 2,
+                    from: 6,
+                    to: 4
+                }],
+                report: vec![9, 7, 4, 2, 1],
+            })
+        );
+        assert_eq!(repair(&[1, 5, 3, 9, 1, 2], 1, &policy), None);
+        assert_eq!(
+            repair(&[1, 5, 3, 9, 1, 2], 3, &policy).map(|repair| repair.edits.len()),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn replacement_values() {
+        let policy = SafetyPolicy::default();
+
+        // The last edit: 2 to 4 fit both going up, nothing fits both going down.
+        assert_eq!(replacements(Some(1), Some(5), true, &policy), [2]);
+        assert_eq!(replacements(Some(1), Some(9), true, &policy), []);
+        assert_eq!(replacements(None, Some(0), true, &policy), [1]);
+        assert_eq!(replacements(Some(10), None, true, &policy), [7, 11]);
+
+        // More edits can follow, so everything one step away from a neighbour.
+        assert_eq!(
+            replacements(Some(1), Some(9), false, &policy),
+            [0, 2, 3, 4, 6, 7, 8, 10, 11, 12]
+        );
+        assert_eq!(replacements(None, Some(0), false, &policy), [1, 2, 3]);
+    }
+
+    // Two levels next to each other both have to be replaced.
+    #[test]
+    fn adjacent_replacements() {
+        let policy = SafetyPolicy::default();
+        let wider = SafetyPolicy {
+            min_step: 2,
+            max_step: 5,
+            ..SafetyPolicy::default()
+        };
+
+        for (report, policy) in [
+            (&[34, 27, 25, 27, 26, 24][..], &policy),
+            (&[5, 14, 5, 14], &policy),
+            (&[18, 9, 18, 9], &policy),
+            (&[27, 25, 16, 25, 17, 15], &policy),
+            (&[10, 22, 5, 18, 21, 23], &policy),
+            (&[9, 22, 5, 22, 24], &wider),
+        ] {
+            let found = repair(report, 2, policy).unwrap();
+
+            assert_eq!(found.edits.len(), 2, "{report:?}");
+            assert!(policy.is_safe_report(&found.report));
+        }
+
+        assert_eq!(
+            repair(&[5, 14, 5, 14], 2, &policy).map(|repair| repair.report),
+            Some(vec![5, 8, 11, 14])
+        );
+    }
+
+    // Every value one step away from a neighbour, for every edit.
+    fn brute_force(report: &[u32], max_edits: usize, policy: &SafetyPolicy) -> Option<usize> {
+        let mut frontier = vec![report.to_vec()];
+        let mut seen: HashSet<Vec<u32>> = HashSet::from([report.to_vec()]);
+
+        for edits in 0..=max_edits {
+            if frontier.iter().any(|report| policy.is_safe_report(report)) {
+                return Some(edits);
+            }
+
+            if edits == max_edits {
+                break;
+            }
+
+            let mut next: Vec<Vec<u32>> = vec![];
+
+            for report in &frontier {
+                for edit in candidate_edits(report, false, policy) {
+                    let child = edit.apply(report);
+                    if seen.insert(child.clone()) {
+                        next.push(child);
+                    }
+                }
+            }
+
+            frontier = next;
+        }
+
+        None
+    }
+
+    #[test]
+    fn same_as_brute_force() {
+        let policies = [
+            SafetyPolicy::default(),
+            SafetyPolicy {
+                min_step: 2,
+                max_step: 5,
+                ..SafetyPolicy::default()
+            },
+            SafetyPolicy {
+                min_step: 0,
+                strict: false,
+                ..SafetyPolicy::default()
+            },
+        ];
+
+        // xorshift, so that the reports are the same on every run.
+        let mut state = 0x2545f4914f6cdd1du64;
+        let mut random = |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+
+        for _ in 0..1000 {
+            let len = 3 + random(4) as usize;
+            let report = (0..len).map(|_| random(30) as u32).collect::<Vec<u32>>();
+            let policy = &policies[random(3) as usize];
+
+            let found = repair(&report, 2, policy);
+            if let Some(found) = &found {
+                assert!(policy.is_safe_report(&found.report));
+                assert_eq!(
+                    found
+                        .edits
+                        .iter()
+                        .fold(report.clone(), |report, edit| edit.apply(&report)),
+                    found.report
+                );
+            }
+
+            assert_eq!(
+                found.map(|found| found.edits.len()),
+                brute_force(&report, 2, policy),
+                "{report:?} {policy}"
+            );
+        }
+    }
+
+    #[test]
+    fn wide_step_ranges() {
+        let policy = SafetyPolicy {
+            max_step: 20000,
+            ..SafetyPolicy::default()
+        };
+
+        assert_eq!(repair(&[1, 5, 3, 9, 1, 2], 2, &policy), None);
+    }
+}
@@ -0,0 +1,147 @@
+// Render mode: draws each report as a one line sparkline, one character per level, scaled
+// between the report's lowest and highest level.
+//
+// In a terminal the levels of the violating step are red and the level removed by the Problem
+// Dampener is struck through. Without colors the same marks go on a second line below the
+// sparkline: '^' for the violating step and 'x' for the removed level.
+
+use crate::verdict::Verdict;
+
+const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const ASCII: [char; 8] = ['_', '.', ',', '-', '~', '=', '*', '#'];
+
+const RED: &str = "\x1b[31m";
+const STRIKE: &str = "\x1b[9;2m";
+const RESET: &str = "\x1b[0m";
+
+fn scale(report: &[u32], ramp: &[char; 8]) -> Vec<char> {
+    let lowest = report.iter().copied().min().unwrap_or(0);
+    let highest = report.iter().copied().max().unwrap_or(0);
+    let range = (highest - lowest) as u64;
+
+    report
+        .iter()
+        .map(|&level| {
+            // A flat report has no range to scale by, it's drawn in the middle.
+            let position = ((level - lowest) as u64 * (ramp.len() as u64 - 1))
+                .checked_div(range)
+                .unwrap_or(ramp.len() as u64 / 2);
+            ramp[position as usize]
+        })
+        .collect()
+}
+
+fn verdict_text(verdict: &Verdict) -> String {
+    match verdict {
+        Verdict::Safe => "Safe".to_string(),
+        Verdict::Dampened { violation, .. } => format!("Safe alternative, {violation}"),
+        Verdict::Unsafe { violation } => format!("Unsafe, {violation}"),
+    }
+}
+
+pub fn render(report: &[u32], verdict: &Verdict, color: bool) -> String {
+    let (offending, removed) = match verdict {
+        Verdict::Safe => (vec![], vec![]),
+        Verdict::Dampened {
+            violation,
+            removals,
+        } => (violation.levels(), removals[0].clone()),
+        Verdict::Unsafe { violation } => (violation.levels(), vec![]),
+    };
+
+    let mut result = String::new();
+
+    if color {
+        for (index, block) in scale(report, &BLOCKS).into_iter().enumerate() {
+            let style = if removed.contains(&index) {
+                Some(STRIKE)
+            } else if offending.contains(&index) {
+                Some(RED)
+            } else {
+                None
+            };
+
+            match style {
+                Some(style) => result.push_str(&format!("{style}{block}{RESET}")),
+                None => result.push(block),
+            }
+        }
+
+        result.push_str(&format!("  {:?}: {}", report, verdict_text(verdict)));
+    } else {
+        let line = scale(report, &ASCII).into_iter().collect::<String>();
+        result.push_str(&format!("{line}  {:?}: {}", report, verdict_text(verdict)));
+
+        if !offending.is_empty() || !removed.is_empty() {
+            let markers = (0..report.len())
+                .map(|index| {
+                    if removed.contains(&index) {
+                        'x'
+                    } else if offending.contains(&index) {
+                        '^'
+                    } else {
+                        ' '
+                    }
+                })
+                .collect::<String>();
+
+            result.push('\n');
+            result.push_str(markers.trim_end());
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::SafetyPolicy;
+    use crate::verdict;
+
+    #[test]
+    fn flat_report_is_drawn_in_the_middle() {
+        assert_eq!(scale(&[5, 5, 5], &ASCII), ['~', '~', '~']);
+        assert_eq!(scale(&[9], &BLOCKS), ['▅']);
+    }
+
+    #[test]
+    fn full_range() {
+        assert_eq!(
+            scale(&[10, 17, 11, 16, 12, 15, 13, 14], &ASCII)
+                .into_iter()
+                .collect::<String>(),
+            "_#.*,=-~"
+        );
+        assert_eq!(scale(&[0, u32::MAX], &BLOCKS), ['▁', '█']);
+    }
+
+    #[test]
+    fn ascii_markers() {
+        let policy = SafetyPolicy::default();
+        let render = |report: &[u32]| render(report, &verdict::judge(report, &policy), false);
+
+        assert_eq!(render(&[1, 3, 6, 7, 9]), "_.~=#  [1, 3, 6, 7, 9]: Safe");
+        assert_eq!(
+            render(&[1, 3, 2, 4, 5]),
+            "_-.=#  [1, 3, 2, 4, 5]: Safe alternative, direction change between index 1 and 2\n x^"
+        );
+        assert_eq!(
+            render(&[1, 2, 7, 8, 9]),
+            "__=*#  [1, 2, 7, 8, 9]: Unsafe, step too large between index 1 and 2 (+5)\n ^^"
+        );
+    }
+
+    #[test]
+    fn colors_replace_the_markers() {
+        let report = [1, 3, 2, 4, 5];
+        let line = render(
+            &report,
+            &verdict::judge(&report, &SafetyPolicy::default()),
+            true,
+        );
+
+        assert!(!line.contains('\n'));
+        assert!(line.starts_with(&format!("▁{STRIKE}▄{RESET}{RED}▂{RESET}▆█")));
+    }
+}
@@ -0,0 +1,240 @@
+// Explains why a report is unsafe and, for the Problem Dampener, which levels can be removed
+// to make it safe.
+
+use std::fmt;
+
+use crate::policy::SafetyPolicy;
+
+// The first rule a report breaks. Indices point into the report, a step "at index i" is the
+// step between levels i and i + 1. Deltas are signed (next level - current level).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Violation {
+    TooShort { len: usize, min: usize },
+    DirectionChange { index: usize },
+    StepTooSmall { index: usize, delta: i64 },
+    StepTooLarge { index: usize, delta: i64 },
+}
+
+impl Violation {
+    // Indices of the levels that take part in the violation.
+    pub fn levels(&self) -> Vec<usize> {
+        match *self {
+            Violation::TooShort { .. } => vec![],
+            Violation::DirectionChange { index }
+            | Violation::StepTooSmall { index, .. }
+            | Violation::StepTooLarge { index, .. } => vec![index, index + 1],
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Violation::TooShort { len, min } => write!(
+                f,
+                "report too short ({len} levels, at least {min} required)"
+            ),
+            Violation::DirectionChange { index } => write!(
+                f,
+                "direction change between index {index} and {}",
+                index + 1
+            ),
+            Violation::StepTooSmall { index, delta } => write!(
+                f,
+                "step too small between index {index} and {} ({delta:+})",
+                index + 1
+            ),
+            Violation::StepTooLarge { index, delta } => write!(
+                f,
+                "step too large between index {index} and {} ({delta:+})",
+                index + 1
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Safe,
+    // Unsafe as is, but safe after removing any one of the listed sets of level indices.
+    Dampened {
+        violation: Violation,
+        removals: Vec<Vec<usize>>,
+    },
+    Unsafe {
+        violation: Violation,
+    },
+}
+
+impl Verdict {
+    pub fn is_safe(&self) -> bool {
+        !matches!(self, Verdict::Unsafe { .. })
+    }
+}
+
+// Calls `visit` with every set of `k` indices out of 0..n, in lexicographic order.
+fn for_each_combination(n: usize, k: usize, visit: &mut impl FnMut(&[usize])) {
+    fn helper(
+        start: usize,
+        n: usize,
+        k: usize,
+        current: &mut Vec<usize>,
+        visit: &mut impl FnMut(&[usize]),
+    ) {
+        if current.len() == k {
+            visit(current);
+            return;
+        }
+
+        for i in start..n {
+            current.push(i);
+            helper(i + 1, n, k, current, visit);
+            current.pop();
+        }
+    }
+
+    helper(0, n, k, &mut Vec::with_capacity(k), visit);
+}
+
+// Returns every smallest set of level indices whose removal makes the report safe, trying one
+// removal first, then two and so on up to the policy's limit.
+pub fn find_removals(report: &[u32], policy: &SafetyPolicy) -> Vec<Vec<usize>> {
+    let mut result: Vec<Vec<usize>> = vec![];
+    let mut alternative: Vec<u32> = Vec::with_capacity(report.len());
+
+    for k in 1..=policy.max_removals.min(report.len()) {
+        for_each_combination(report.len(), k, &mut |removed| {
+            alternative.clear();
+            alternative.extend(
+                report
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| !removed.contains(index))
+                    .map(|(_, level)| *level),
+            );
+
+            if policy.is_safe_report(&alternative) {
+                result.push(removed.to_vec());
+            }
+        });
+
+        if !result.is_empty() {
+            break;
+        }
+    }
+
+    result
+}
+
+pub fn judge(report: &[u32], policy: &SafetyPolicy) -> Verdict {
+    let violation = match policy.check(report) {
+        Ok(()) => return Verdict::Safe,
+        Err(violation) => violation,
+    };
+
+    let removals = find_removals(report, policy);
+    if removals.is_empty() {
+        Verdict::Unsafe { violation }
+    } else {
+        Verdict::Dampened {
+            violation,
+            removals,
+        }
+    }
+}
+
+// Prints the levels on one line and marks the offending ones with '^' on the line below:
+//
+//   1 2 7 8 9
+//     ^ ^
+pub fn render(report: &[u32], violation: &Violation) -> String {
+    let offending = violation.levels();
+    let mut levels = String::new();
+    let mut markers = String::new();
+
+    for (index, level) in report.iter().enumerate() {
+        if index > 0 {
+            levels.push(' ');
+            markers.push(' ');
+        }
+
+        let text = level.to_string();
+        let marker = if offending.contains(&index) { '^' } else { ' ' };
+        markers.push(marker);
+        markers.extend(std::iter::repeat_n(' ', text.len() - 1));
+        levels.push_str(&text);
+    }
+
+    format!("{levels}\n{}", markers.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_removal_is_listed() {
+        let policy = SafetyPolicy::default();
+
+        assert_eq!(find_removals(&[1, 3, 2, 4, 5], &policy), [[1], [2]]);
+        assert_eq!(find_removals(&[8, 6, 4, 4, 1], &policy), [[2], [3]]);
+        assert_eq!(
+            find_removals(&[1, 2, 7, 8, 9], &policy),
+            Vec::<Vec<usize>>::new()
+        );
+    }
+
+    #[test]
+    fn smallest_removals_only() {
+        let policy = SafetyPolicy {
+            max_removals: 2,
+            ..SafetyPolicy::default()
+        };
+
+        // One removal is enough, no pair is listed.
+        assert_eq!(find_removals(&[1, 3, 2, 4, 5], &policy), [[1], [2]]);
+        assert_eq!(find_removals(&[1, 9, 9, 2, 3], &policy), [[1, 2]]);
+    }
+
+    #[test]
+    fn judge_example() {
+        let policy = SafetyPolicy::default();
+
+        assert_eq!(judge(&[7, 6, 4, 2, 1], &policy), Verdict::Safe);
+        assert_eq!(
+            judge(&[1, 3, 2, 4, 5], &policy),
+            Verdict::Dampened {
+                violation: Violation::DirectionChange { index: 1 },
+                removals: vec![vec![1], vec![2]],
+            }
+        );
+        assert_eq!(
+            judge(&[1, 2, 7, 8, 9], &policy),
+            Verdict::Unsafe {
+                violation: Violation::StepTooLarge { index: 1, delta: 5 },
+            }
+        );
+    }
+
+    #[test]
+    fn markers_line_up_with_levels() {
+        assert_eq!(
+            render(
+                &[1, 2, 7, 8, 9],
+                &Violation::StepTooLarge { index: 1, delta: 5 }
+            ),
+            "1 2 7 8 9\n  ^ ^"
+        );
+        assert_eq!(
+            render(
+                &[100, 7, 12, 1000],
+                &Violation::StepTooLarge {
+                    index: 2,
+                    delta: 988
+                }
+            ),
+            "100 7 12 1000\n      ^  ^"
+        );
+        assert_eq!(render(&[5], &Violation::TooShort { len: 1, min: 2 }), "5\n");
+    }
+}
@@ -0,0 +1,346 @@
+// Annotate mode: prints the memory back with the instructions highlighted. Instructions that
+// compute a value are green, do() is cyan, don't() is magenta and reset() is yellow. Everything
+// after a don't() is dimmed until the next do(). Near misses, things that look like an
+// instruction but aren't one (like "mul[3,7]" or "mul ( 2 , 4 )"), are underlined.
+//
+// The output is either ANSI escape codes for a terminal or a standalone HTML page. When the
+// output isn't a terminal, the memory is printed as it is, without escape codes.
+
+use crate::instructions::{InstructionSet, Semantics};
+use crate::lexer::Spanned;
+use crate::util::DoDontState;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mark {
+    Noise,
+    Value,
+    Enable,
+    Disable,
+    Reset,
+    NearMiss,
+}
+
+// A run of bytes that are all printed the same way.
+struct Region {
+    start: usize,
+    end: usize,
+    mark: Mark,
+    enabled: bool,
+}
+
+// How far after a name a near miss can close its brackets.
+const NEAR_MISS_LENGTH: usize = 24;
+
+// Looks for a name followed by some kind of bracket, possibly after whitespace, holding
+// nothing but digits, signs, separators and whitespace. Returns the length of the near miss.
+fn near_miss(input: &[u8], names: &[&str]) -> Option<usize> {
+    names
+        .iter()
+        .filter(|name| input.starts_with(name.as_bytes()))
+        .find_map(|name| near_miss_after(input, name.len()))
+}
+
+fn near_miss_after(input: &[u8], mut pos: usize) -> Option<usize> {
+    pos += input[pos..]
+        .iter()
+        .take_while(|byte| byte.is_ascii_whitespace())
+        .count();
+
+    if !b"([{<".contains(input.get(pos)?) {
+        return None;
+    }
+
+    let inside = input[pos + 1..]
+        .iter()
+        .take(NEAR_MISS_LENGTH)
+        .take_while(|&&byte| {
+            byte.is_ascii_digit() || byte.is_ascii_whitespace() || b"+-,.;:".contains(&byte)
+        })
+        .count();
+
+    // Brackets don't have to match, "mul(32,64]" is a near miss too.
+    if !b")]}>".contains(input.get(pos + 1 + inside)?) {
+        return None;
+    }
+
+    Some(pos + inside + 2)
+}
+
+fn push(result: &mut Vec<Region>, start: usize, end: usize, mark: Mark, enabled: bool) {
+    if start < end {
+        result.push(Region {
+            start,
+            end,
+            mark,
+            enabled,
+        });
+    }
+}
+
+fn regions(memory: &[u8], tokens: &[Spanned], instructions: &InstructionSet) -> Vec<Region> {
+    let names = instructions.names();
+    let mut result: Vec<Region> = vec![];
+    let mut state = DoDontState::new();
+    let mut pos = 0;
+
+    // The gap before each token (and after the last one) is searched for near misses.
+    let ends = tokens.iter().map(Some).chain(std::iter::once(None));
+
+    for token in ends {
+        let gap_end = token.map_or(memory.len(), |token| token.span.start);
+        let mut noise = pos;
+
+        while pos < gap_end {
+            match near_miss(&memory[pos..gap_end], &names) {
+                Some(len) => {
+                    push(&mut result, noise, pos, Mark::Noise, state.is_enabled());
+                    push(
+                        &mut result,
+                        pos,
+                        pos + len,
+                        Mark::NearMiss,
+                        state.is_enabled(),
+                    );
+                    pos += len;
+                    noise = pos;
+                }
+                None => pos += 1,
+            }
+        }
+
+        push(&mut result, noise, gap_end, Mark::Noise, state.is_enabled());
+
+        let Some(Spanned { token, span }) = token else {
+            break;
+        };
+
+        let semantics = instructions.semantics(token);
+
+        let mark = match semantics {
+            Semantics::Value(_) => Mark::Value,
+            Semantics::Enable => Mark::Enable,
+            Semantics::Disable => Mark::Disable,
+            Semantics::Reset => Mark::Reset,
+        };
+
+        // do() and don't() are drawn in the state they switch to.
+        state.apply(&semantics, span.start);
+        push(&mut result, span.start, span.end, mark, state.is_enabled());
+        pos = span.end;
+    }
+
+    result
+}
+
+// Control characters would mess with the terminal, they're printed as '.'.
+fn printable(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .chars()
+        .map(|char| {
+            if char.is_control() && char != '\n' && char != '\t' {
+                '.'
+            } else {
+                char
+            }
+        })
+        .collect()
+}
+
+pub fn ansi(
+    memory: &[u8],
+    tokens: &[Spanned],
+    instructions: &InstructionSet,
+    color: bool,
+) -> String {
+    let mut result = String::new();
+
+    for region in regions(memory, tokens, instructions) {
+        let text = printable(&memory[region.start..region.end]);
+        if !color {
+            result.push_str(&text);
+            continue;
+        }
+
+        let mut codes: Vec<&str> = vec![];
+
+        // Bold and dim can't be combined, disabled instructions are only colored.
+        match (region.mark, region.enabled) {
+            (Mark::Noise | Mark::NearMiss, true) => {}
+            (_, true) => codes.push("1"),
+            (_, false) => codes.push("2"),
+        }
+
+        match region.mark {
+            Mark::Noise => {}
+            Mark::Value => codes.push("32"),
+            Mark::Enable => codes.push("36"),
+            Mark::Disable => codes.push("35"),
+            Mark::Reset => codes.push("33"),
+            Mark::NearMiss => codes.push("4"),
+        }
+
+        if codes.is_empty() {
+            result.push_str(&text);
+        } else {
+            result.push_str(&format!("\x1b[{}m{text}\x1b[0m", codes.join(";")));
+        }
+    }
+
+    result
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn html(memory: &[u8], tokens: &[Spanned], instructions: &InstructionSet) -> String {
+    let mut body = String::new();
+
+    for region in regions(memory, tokens, instructions) {
+        let class = match region.mark {
+            Mark::Noise => "",
+            Mark::Value => "value",
+            Mark::Enable => "enable",
+            Mark::Disable => "disable",
+            Mark::Reset => "reset",
+            Mark::NearMiss => "near-miss",
+        };
+
+        let classes = match (class, region.enabled) {
+            ("", true) => String::new(),
+            ("", false) => "disabled".to_string(),
+            (class, true) => class.to_string(),
+            (class, false) => format!("{class} disabled"),
+        };
+
+        let text = escape_html(&printable(&memory[region.start..region.end]));
+
+        if classes.is_empty() {
+            body.push_str(&text);
+        } else {
+            body.push_str(&format!("<span class=\"{classes}\">{text}</span>"));
+        }
+    }
+
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>Day 3 memory</title>
+<style>
+pre {{ white-space: pre-wrap; word-break: break-all; }}
+.value {{ color: #1a7f37; font-weight: bold; }}
+.enable {{ color: #0a7ea4; font-weight: bold; }}
+.disable {{ color: #a626a4; font-weight: bold; }}
+.reset {{ color: #b08800; font-weight: bold; }}
+.near-miss {{ text-decoration: underline; }}
+.disabled {{ opacity: 0.45; }}
+</style>
+</head>
+<body>
+<pre>{body}</pre>
+</body>
+</html>
+"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{Grammar, Lexer};
+
+    const PART_1: &[u8] =
+        b"xmul(2,4)%&mul[3,7]!@^do_not_mul(5,5)+mul(32,64]then(mul(11,8)mul(8,5))";
+    const PART_2: &[u8] =
+        b"xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+
+    fn marks(memory: &[u8]) -> Vec<(&str, Mark, bool)> {
+        let instructions = InstructionSet::standard();
+        let tokens = Lexer::new(Grammar::Strict, &instructions).tokenize(memory);
+
+        regions(memory, &tokens, &instructions)
+            .into_iter()
+            .map(|region| {
+                let text = std::str::from_utf8(&memory[region.start..region.end]).unwrap();
+                (text, region.mark, region.enabled)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn near_misses() {
+        let names = ["mul", "do", "don't"];
+
+        assert_eq!(near_miss(b"mul[3,7]!", &names), Some(8));
+        assert_eq!(near_miss(b"mul ( 2 , 4 )x", &names), Some(13));
+        assert_eq!(near_miss(b"mul(32,64]then", &names), Some(10));
+        assert_eq!(near_miss(b"mul(2,x)", &names), None);
+        assert_eq!(near_miss(b"mul[3,7", &names), None);
+        assert_eq!(near_miss(b"mulch(1)", &names), None);
+        assert_eq!(near_miss(b"xmul[3,7]", &names), None);
+    }
+
+    #[test]
+    fn part_1_example() {
+        let near_misses = marks(PART_1)
+            .into_iter()
+            .filter(|(_, mark, _)| *mark == Mark::NearMiss)
+            .map(|(text, _, _)| text)
+            .collect::<Vec<&str>>();
+        assert_eq!(near_misses, ["mul[3,7]", "mul(32,64]"]);
+
+        assert_eq!(
+            marks(b"mul ( 2 , 4 )mul(2,4)"),
+            [
+                ("mul ( 2 , 4 )", Mark::NearMiss, true),
+                ("mul(2,4)", Mark::Value, true)
+            ]
+        );
+    }
+
+    // Everything from a don't() to the next do() is disabled, the toggles are drawn in the
+    // state they switch to.
+    #[test]
+    fn part_2_example() {
+        assert_eq!(
+            marks(PART_2),
+            [
+                ("x", Mark::Noise, true),
+                ("mul(2,4)", Mark::Value, true),
+                ("&", Mark::Noise, true),
+                ("mul[3,7]", Mark::NearMiss, true),
+                ("!^", Mark::Noise, true),
+                ("don't()", Mark::Disable, false),
+                ("_", Mark::Noise, false),
+                ("mul(5,5)", Mark::Value, false),
+                ("+", Mark::Noise, false),
+                ("mul(32,64]", Mark::NearMiss, false),
+                ("(", Mark::Noise, false),
+                ("mul(11,8)", Mark::Value, false),
+                ("un", Mark::Noise, false),
+                ("do()", Mark::Enable, true),
+                ("?", Mark::Noise, true),
+                ("mul(8,5)", Mark::Value, true),
+                (")", Mark::Noise, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn escape_codes_only_with_color() {
+        let instructions = InstructionSet::standard();
+        let tokens = Lexer::new(Grammar::Strict, &instructions).tokenize(PART_2);
+
+        let plain = ansi(PART_2, &tokens, &instructions, false);
+        assert_eq!(plain.as_bytes(), PART_2);
+
+        let colored = ansi(PART_2, &tokens, &instructions, true);
+        assert!(colored.starts_with("x\x1b[1;32mmul(2,4)\x1b[0m&\x1b[4mmul[3,7]\x1b[0m"));
+        assert!(colored.contains("\x1b[2;35mdon't()\x1b[0m"));
+    }
+}
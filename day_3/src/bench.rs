@@ -0,0 +1,72 @@
+// Benchmark mode: times the lexer and the fast scanner (see fast.rs) on the same memory and
+// prints how many GB/s each of them scans. input.txt is repeated until the memory has the
+// requested size. Both have to find exactly the same instructions, or the benchmark fails.
+//
+// Run it with "cargo run --release", the numbers of a debug build mean nothing.
+
+use std::time::{Duration, Instant};
+
+use crate::fast::FastScanner;
+use crate::lexer::{Lexer, Spanned};
+
+pub const DEFAULT_MEGABYTES: usize = 64;
+
+// Every scanner is run a few times, the fastest run is reported.
+const RUNS: usize = 3;
+
+fn time(mut run: impl FnMut() -> Vec<Spanned>) -> (Vec<Spanned>, Duration) {
+    let mut best = Duration::MAX;
+    let mut result: Vec<Spanned> = vec![];
+
+    for _ in 0..RUNS {
+        // The previous result is dropped outside of the timed part.
+        let start = Instant::now();
+        let found = run();
+        best = best.min(start.elapsed());
+        result = found;
+    }
+
+    (result, best)
+}
+
+fn throughput(bytes: usize, elapsed: Duration) -> f64 {
+    bytes as f64 / elapsed.as_secs_f64() / 1e9
+}
+
+// Returns true if both scanners found the same instructions.
+pub fn run(lexer: &Lexer, input: &[u8], megabytes: usize) -> bool {
+    let size = megabytes * 1024 * 1024;
+    let memory = input
+        .iter()
+        .copied()
+        .cycle()
+        .take(size)
+        .collect::<Vec<u8>>();
+
+    let fast = FastScanner::new(lexer);
+    let (expected, reference) = time(|| lexer.tokenize(&memory));
+    let (found, accelerated) = time(|| fast.tokenize(&memory));
+
+    println!("Memory: {megabytes} MB, {} instruction(s)", expected.len());
+    println!(
+        "Lexer:        {:8.3} GB/s ({reference:?})",
+        throughput(memory.len(), reference)
+    );
+    println!(
+        "Fast scanner: {:8.3} GB/s ({accelerated:?})",
+        throughput(memory.len(), accelerated)
+    );
+
+    if found != expected {
+        let index = expected
+            .iter()
+            .zip(&found)
+            .position(|(expected, found)| expected != found)
+            .unwrap_or(expected.len().min(found.len()));
+
+        println!("FAIL: the scanners differ at instruction {index}");
+        return false;
+    }
+
+    true
+}
@@ -0,0 +1,226 @@
+// Runs the conformance corpus (conformance.txt) against both grammars and reports every case
+// where the instructions found differ from the expected ones. Cases can also say what the
+// strict grammar finds in expression mode.
+//
+// Every memory is also read in two pieces, split at every possible byte offset, and in chunks
+// of one byte. Streaming and the fast scanner have to find exactly the same instructions, at
+// the same offsets, as scanning the whole memory at once.
+
+use std::fmt;
+use std::io::Read;
+use std::iter::Peekable;
+
+use crate::fast::FastScanner;
+use crate::instructions::InstructionSet;
+use crate::lexer::{self, Grammar, Lexer, Spanned};
+use crate::stream;
+
+struct Case {
+    line: usize,
+    memory: Vec<u8>,
+    strict: String,
+    lenient: Option<String>,
+    expressions: Option<String>,
+}
+
+// Reads the "key: instructions" line that must follow.
+fn expect<'a>(
+    key: &str,
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+) -> Result<String, String> {
+    let (index, line) = lines.next().ok_or(format!("missing '{key}:' line"))?;
+
+    line.strip_prefix(key)
+        .and_then(|rest| rest.strip_prefix(':'))
+        .map(|rest| rest.trim().to_string())
+        .ok_or(format!("line {}: expected '{key}:'", index + 1))
+}
+
+// Memories are written as text, with "\xHH" for any byte and "\\" for a backslash.
+fn unescape(memory: &str) -> Result<Vec<u8>, String> {
+    let mut result: Vec<u8> = vec![];
+    let mut rest = memory.as_bytes();
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+
+        if byte != b'\\' {
+            result.push(byte);
+            continue;
+        }
+
+        if let Some(tail) = rest.strip_prefix(b"\\") {
+            result.push(b'\\');
+            rest = tail;
+            continue;
+        }
+
+        let hex = rest
+            .strip_prefix(b"x")
+            .and_then(|tail| tail.get(..2))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .ok_or(format!("invalid escape in '{memory}'"))?;
+
+        result.push(hex);
+        rest = &rest[3..];
+    }
+
+    Ok(result)
+}
+
+// The other way around, for writing new cases. Only printable ASCII is kept as is.
+pub fn escape(memory: &[u8]) -> String {
+    memory
+        .iter()
+        .map(|&byte| match byte {
+            b'\\' => "\\\\".to_string(),
+            b' '..=b'~' => (byte as char).to_string(),
+            _ => format!("\\x{byte:02x}"),
+        })
+        .collect()
+}
+
+// Reads the "key: instructions" line if it's the next one.
+fn optional<'a>(
+    key: &str,
+    lines: &mut Peekable<impl Iterator<Item = (usize, &'a str)>>,
+) -> Result<Option<String>, String> {
+    match lines.peek() {
+        Some((_, line)) if line.starts_with(&format!("{key}:")) => expect(key, lines).map(Some),
+        _ => Ok(None),
+    }
+}
+
+fn parse_corpus(content: &str) -> Result<Vec<Case>, String> {
+    let mut result: Vec<Case> = vec![];
+    let mut lines = content.lines().enumerate().peekable();
+
+    while let Some((index, line)) = lines.next() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let memory = line
+            .strip_prefix("memory: ")
+            .ok_or(format!("line {}: expected 'memory: '", index + 1))?;
+
+        result.push(Case {
+            line: index + 1,
+            memory: unescape(memory).map_err(|err| format!("line {}: {err}", index + 1))?,
+            strict: expect("strict", &mut lines)?,
+            lenient: optional("lenient", &mut lines)?,
+            expressions: optional("expressions", &mut lines)?,
+        });
+    }
+
+    Ok(result)
+}
+
+// The instructions as the corpus writes them, separated by spaces.
+pub fn canonical(tokens: impl IntoIterator<Item = impl fmt::Display>) -> String {
+    let tokens = tokens
+        .into_iter()
+        .map(|token| token.to_string())
+        .collect::<Vec<String>>();
+
+    tokens.join(" ")
+}
+
+// Returns the first way of reading the memory in pieces that finds different instructions.
+fn check_splits(lexer: &Lexer, memory: &[u8], expected: &[Spanned]) -> Option<String> {
+    for offset in 0..=memory.len() {
+        let (first, second) = memory.split_at(offset);
+        if stream::collect(lexer, first.chain(second), usize::MAX).unwrap() != expected {
+            return Some(format!("split at offset {offset}"));
+        }
+    }
+
+    if stream::collect(lexer, memory, 1).unwrap() != expected {
+        return Some("chunks of 1 byte".to_string());
+    }
+
+    None
+}
+
+// Returns true if every case passed.
+pub fn run(path: &str) -> Result<bool, String> {
+    let content =
+        std::fs::read_to_string(path).map_err(|err| format!("cannot read '{path}': {err}"))?;
+    let cases = parse_corpus(&content)?;
+    let instructions = InstructionSet::standard();
+    let mut failures = 0;
+    let mut checks = 0;
+
+    for case in &cases {
+        let mut lexers = vec![(
+            "Strict",
+            Lexer::new(Grammar::Strict, &instructions),
+            &case.strict,
+        )];
+
+        if let Some(expected) = &case.lenient {
+            lexers.push((
+                "Lenient",
+                Lexer::new(Grammar::Lenient, &instructions),
+                expected,
+            ));
+        }
+
+        if let Some(expected) = &case.expressions {
+            let lexer =
+                Lexer::new(Grammar::Strict, &instructions).expressions(lexer::DEFAULT_MAX_DEPTH);
+            lexers.push(("Expressions", lexer, expected));
+        }
+
+        for (grammar, lexer, expected) in lexers {
+            checks += 3;
+
+            let tokens = lexer.tokenize(&case.memory);
+            let found = canonical(tokens.iter().map(|spanned| &spanned.token));
+
+            if found != *expected {
+                failures += 1;
+                println!(
+                    "FAIL line {} ({grammar}): {}\n  expected: {expected}\n  found:    {found}",
+                    case.line,
+                    String::from_utf8_lossy(&case.memory)
+                );
+            }
+
+            if let Some(split) = check_splits(&lexer, &case.memory, &tokens) {
+                failures += 1;
+                println!(
+                    "FAIL line {} ({grammar}): {}\n  streaming differs, {split}",
+                    case.line,
+                    String::from_utf8_lossy(&case.memory)
+                );
+            }
+
+            if FastScanner::new(&lexer).tokenize(&case.memory) != tokens {
+                failures += 1;
+                println!(
+                    "FAIL line {} ({grammar}): {}\n  the fast scanner differs",
+                    case.line,
+                    String::from_utf8_lossy(&case.memory)
+                );
+            }
+        }
+    }
+
+    println!(
+        "{} case(s), {checks} check(s), {failures} failure(s)",
+        cases.len()
+    );
+
+    Ok(failures == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    // Every case of the corpus, with every grammar, split and scanner it asks for.
+    #[test]
+    fn corpus_passes() {
+        assert_eq!(super::run("conformance.txt"), Ok(true));
+    }
+}
@@ -0,0 +1,61 @@
+// A faster way to find the same instructions as Lexer::tokenize. The lexer tries to read an
+// instruction at every single byte, but an instruction can only start where one of the
+// registered names is followed by '('. So instead of looking for every name, the scanner
+// looks for the one byte they all share: the '(' is found with memchr, which uses SIMD to
+// skip over everything else, and only the bytes right before it are compared to the names.
+// Each name that matches there is a candidate, and only candidates are read by the lexer.
+//
+// Names can't hold a '(', so the candidates come out in the same order as the lexer would
+// find them. If the lexer doesn't recognize anything at a candidate, the next one is tried,
+// just like the lexer would go on with the next byte, so the result is always the same.
+
+use crate::lexer::{Lexer, Spanned};
+
+pub struct FastScanner<'a> {
+    lexer: &'a Lexer<'a>,
+    // Longest first, so the candidates in front of the same '(' come out left to right.
+    names: Vec<&'static str>,
+}
+
+impl<'a> FastScanner<'a> {
+    pub fn new(lexer: &'a Lexer<'a>) -> Self {
+        let mut names = lexer.instructions.names();
+        names.sort_by_key(|name| std::cmp::Reverse(name.len()));
+
+        FastScanner { lexer, names }
+    }
+
+    pub fn tokenize(&self, memory: &[u8]) -> Vec<Spanned> {
+        let mut result: Vec<Spanned> = vec![];
+        // Nothing before `pos` is part of a candidate anymore, it's inside the last token.
+        let mut pos = 0;
+        let mut search = 0;
+
+        while let Some(found) = memchr::memchr(b'(', &memory[search..]) {
+            let paren = search + found;
+            search = paren + 1;
+
+            for name in &self.names {
+                let Some(start) = paren.checked_sub(name.len()) else {
+                    continue;
+                };
+
+                if start < pos || &memory[start..paren] != name.as_bytes() {
+                    continue;
+                }
+
+                if let Some((token, len)) = self.lexer.recognize(&memory[start..]) {
+                    result.push(Spanned {
+                        token,
+                        span: start..start + len,
+                    });
+                    pos = start + len;
+                    search = pos;
+                    break;
+                }
+            }
+        }
+
+        result
+    }
+}
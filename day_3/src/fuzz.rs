@@ -0,0 +1,528 @@
+// Differential fuzzing: random memories go through every way of scanning the memory (the
+// lexer, the streaming scanner with a few chunk sizes and the fast scanner) and through a
+// reference matcher that reads the puzzle as plainly as possible. They all have to find the same
+// instructions at the same offsets. This is done for each mode the conformance corpus covers:
+// the strict grammar, the lenient grammar and expression mode, all with the standard
+// instructions. The extended set isn't fuzzed, the corpus couldn't keep its failures.
+//
+// A memory where they don't is made as small as possible while it still fails, printed and
+// added to the conformance corpus, so it stays covered by --conformance after it's fixed.
+
+use std::io::Write;
+
+use crate::conformance;
+use crate::fast::FastScanner;
+use crate::generate::Rng;
+use crate::instructions::InstructionSet;
+use crate::lexer::{self, Grammar, Lexer};
+use crate::stream;
+
+pub const DEFAULT_ITERATIONS: usize = 100_000;
+
+// An instruction and where it starts, in its canonical form.
+type Found = Vec<(usize, String)>;
+
+// One way of reading the memory, like the corpus has a line for.
+#[derive(Clone, Copy)]
+struct Mode {
+    name: &'static str,
+    lenient: bool,
+    max_depth: usize,
+}
+
+const MODES: &[Mode] = &[
+    Mode {
+        name: "strict",
+        lenient: false,
+        max_depth: 0,
+    },
+    Mode {
+        name: "lenient",
+        lenient: true,
+        max_depth: 0,
+    },
+    Mode {
+        name: "expressions",
+        lenient: false,
+        max_depth: lexer::DEFAULT_MAX_DEPTH,
+    },
+];
+
+fn skip_whitespace(input: &[u8]) -> usize {
+    input
+        .iter()
+        .take_while(|byte| byte.is_ascii_whitespace())
+        .count()
+}
+
+// A number at the start of `input` and how many bytes it took: 1 to 3 digits, or in the lenient
+// grammar whitespace, an optional '+', any number of digits that fit in a u32 and whitespace.
+fn reference_number(input: &[u8], mode: Mode) -> Option<(u32, usize)> {
+    let mut pos = 0;
+
+    if mode.lenient {
+        pos += skip_whitespace(input);
+        if input.get(pos) == Some(&b'+') {
+            pos += 1;
+        }
+    }
+
+    let digits = input[pos..]
+        .iter()
+        .take_while(|byte| byte.is_ascii_digit())
+        .count();
+    if digits == 0 || (!mode.lenient && digits > 3) {
+        return None;
+    }
+
+    let value = std::str::from_utf8(&input[pos..pos + digits])
+        .unwrap()
+        .parse::<u32>()
+        .ok()?;
+    pos += digits;
+
+    if mode.lenient {
+        pos += skip_whitespace(&input[pos..]);
+    }
+
+    Some((value, pos))
+}
+
+// A number, or in expression mode a mul() nested less than max_depth deep, with whitespace
+// around it in the lenient grammar.
+fn reference_operand(input: &[u8], mode: Mode, depth: usize) -> Option<(String, usize)> {
+    if let Some((value, len)) = reference_number(input, mode) {
+        return Some((value.to_string(), len));
+    }
+
+    if depth >= mode.max_depth {
+        return None;
+    }
+
+    let mut pos = if mode.lenient {
+        skip_whitespace(input)
+    } else {
+        0
+    };
+    let (nested, len) = reference_mul(&input[pos..], mode, depth + 1)?;
+    pos += len;
+
+    if mode.lenient {
+        pos += skip_whitespace(&input[pos..]);
+    }
+
+    Some((nested, pos))
+}
+
+// mul(X,Y) at the start of `input`, `depth` is how deep it is nested.
+fn reference_mul(input: &[u8], mode: Mode, depth: usize) -> Option<(String, usize)> {
+    let mut pos = b"mul(".len();
+    input.strip_prefix(b"mul(")?;
+
+    let (x, len) = reference_operand(&input[pos..], mode, depth)?;
+    pos += len;
+    if input.get(pos) != Some(&b',') {
+        return None;
+    }
+    pos += 1;
+
+    let (y, len) = reference_operand(&input[pos..], mode, depth)?;
+    pos += len;
+    if input.get(pos) != Some(&b')') {
+        return None;
+    }
+
+    Some((format!("mul({x},{y})"), pos + 1))
+}
+
+// Tries every offset on its own: do(), don't() or mul(X,Y). Once an instruction is found, the
+// search goes on after it.
+fn reference(memory: &[u8], mode: Mode) -> Found {
+    let mut result: Found = vec![];
+    let mut offset = 0;
+
+    while offset < memory.len() {
+        let rest = &memory[offset..];
+
+        let found = if rest.starts_with(b"do()") {
+            Some(("do()".to_string(), 4))
+        } else if rest.starts_with(b"don't()") {
+            Some(("don't()".to_string(), 7))
+        } else {
+            reference_mul(rest, mode, 0)
+        };
+
+        match found {
+            Some((instruction, len)) => {
+                result.push((offset, instruction));
+                offset += len;
+            }
+            None => offset += 1,
+        }
+    }
+
+    result
+}
+
+// Scans a whole memory one way.
+type Scan<'a> = Box<dyn Fn(&[u8]) -> Found + 'a>;
+
+struct Variant<'a> {
+    name: &'static str,
+    scan: Scan<'a>,
+}
+
+fn variants<'a>(lexer: &'a Lexer<'a>) -> Vec<Variant<'a>> {
+    let mut result = vec![
+        Variant {
+            name: "lexer",
+            scan: Box::new(|memory| {
+                lexer
+                    .tokenize(memory)
+                    .into_iter()
+                    .map(|spanned| (spanned.span.start, spanned.token.to_string()))
+                    .collect()
+            }),
+        },
+        Variant {
+            name: "fast scanner",
+            scan: Box::new(|memory| {
+                FastScanner::new(lexer)
+                    .tokenize(memory)
+                    .into_iter()
+                    .map(|spanned| (spanned.span.start, spanned.token.to_string()))
+                    .collect()
+            }),
+        },
+    ];
+
+    for (name, chunk_size) in [("stream, 1 byte chunks", 1), ("stream, 5 byte chunks", 5)] {
+        result.push(Variant {
+            name,
+            scan: Box::new(move |memory| {
+                stream::collect(lexer, memory, chunk_size)
+                    .unwrap()
+                    .into_iter()
+                    .map(|spanned| (spanned.span.start, spanned.token.to_string()))
+                    .collect()
+            }),
+        });
+    }
+
+    result
+}
+
+// Pieces of instructions, so random memories get close to real ones often enough.
+const FRAGMENTS: &[&[u8]] = &[
+    b"mul(",
+    b"do()",
+    b"don't()",
+    b"do(",
+    b"don't(",
+    b"mul",
+    b"do",
+    b"don",
+    b"'t",
+    b"(",
+    b")",
+    b",",
+    b"1",
+    b"12",
+    b"123",
+    b"1234",
+    b"0",
+    b" ",
+    b"+",
+    b"\xff",
+    b"\n",
+    b"\t",
+    b"mul(mul(",
+    b"4294967295",
+    b"4294967296",
+];
+
+// An operand of 0 to 5 digits, so also some that are too long or missing, sometimes with a
+// '+' and whitespace like the lenient grammar takes, or up to 11 digits to overflow a u32.
+fn operand(rng: &mut Rng) -> String {
+    let digits = (0..[rng.below(6), rng.below(12)][rng.below(2) as usize])
+        .map(|_| (b'0' + rng.below(10) as u8) as char)
+        .collect::<String>();
+
+    let mut result = String::new();
+    for piece in [" ", "+", &digits, "\t "] {
+        if piece == digits || rng.below(4) == 0 {
+            result.push_str(piece);
+        }
+    }
+
+    result
+}
+
+// An instruction, mul() can have other mul()s as operands, nested up to 10 deep so also past
+// the depth expression mode takes.
+fn instruction(rng: &mut Rng, depth: usize) -> String {
+    let any_operand = |rng: &mut Rng| {
+        if depth < 10 && rng.below(3) == 0 {
+            instruction(rng, depth + 1)
+        } else {
+            operand(rng)
+        }
+    };
+
+    match rng.below(4) {
+        0 => "do()".to_string(),
+        1 => "don't()".to_string(),
+        _ => format!("mul({},{})", any_operand(rng), any_operand(rng)),
+    }
+}
+
+// mul()s nested around each other 6 to 10 deep, around the most expression mode takes.
+// Random nesting hardly ever gets that deep, with valid operands all the way.
+fn deep_expression(rng: &mut Rng) -> String {
+    let number = |rng: &mut Rng| match rng.below(8) {
+        0 => operand(rng),
+        _ => rng.below(1000).to_string(),
+    };
+
+    let mut result = format!("mul({},{})", number(rng), number(rng));
+
+    for _ in 0..6 + rng.below(5) {
+        result = if rng.below(2) == 0 {
+            format!("mul({result},{})", number(rng))
+        } else {
+            format!("mul({},{result})", number(rng))
+        };
+    }
+
+    result
+}
+
+// Removes, replaces or adds one byte.
+fn mutate(rng: &mut Rng, piece: &mut Vec<u8>) {
+    let at = rng.below(piece.len() as u64 + 1) as usize;
+    let byte = *rng.pick(b"(),'+ 09mdx");
+
+    match (rng.below(3), at < piece.len()) {
+        (0, true) => {
+            piece.remove(at);
+        }
+        (1, true) => piece[at] = byte,
+        _ => piece.insert(at, byte),
+    }
+}
+
+fn random_memory(rng: &mut Rng) -> Vec<u8> {
+    let mut memory: Vec<u8> = vec![];
+
+    for _ in 0..rng.below(12) {
+        match rng.below(7) {
+            0 | 1 => memory.push(rng.next_u64() as u8),
+            2 | 3 => memory.extend_from_slice(rng.pick::<&[u8]>(FRAGMENTS)),
+            _ => {
+                let mut piece = if rng.below(8) == 0 {
+                    deep_expression(rng)
+                } else {
+                    instruction(rng, 0)
+                }
+                .into_bytes();
+                if rng.below(2) == 0 {
+                    mutate(rng, &mut piece);
+                }
+                memory.extend_from_slice(&piece);
+            }
+        }
+    }
+
+    memory
+}
+
+// Removes pieces of the memory, as long as it still fails. Big pieces are tried first, then
+// smaller and smaller ones down to single bytes.
+fn minimize(memory: &[u8], fails: impl Fn(&[u8]) -> bool) -> Vec<u8> {
+    let mut memory = memory.to_vec();
+    let mut len = memory.len() / 2;
+
+    while len > 0 {
+        let mut start = 0;
+
+        while start < memory.len() {
+            let end = (start + len).min(memory.len());
+            let smaller = [&memory[..start], &memory[end..]].concat();
+
+            if fails(&smaller) {
+                memory = smaller;
+            } else {
+                start += len;
+            }
+        }
+
+        len /= 2;
+    }
+
+    memory
+}
+
+fn canonical(found: &Found) -> String {
+    conformance::canonical(found.iter().map(|(_, token)| token))
+}
+
+fn add_to_corpus(path: &str, seed: u64, memory: &[u8]) -> std::io::Result<()> {
+    let mut corpus = std::fs::OpenOptions::new().append(true).open(path)?;
+
+    writeln!(corpus)?;
+    writeln!(corpus, "# Found by --fuzz --seed {seed}.")?;
+    writeln!(corpus, "memory: {}", conformance::escape(memory))?;
+
+    // A line for every mode, with the mode's name as the key.
+    for &mode in MODES {
+        writeln!(
+            corpus,
+            "{}",
+            format!("{}: {}", mode.name, canonical(&reference(memory, mode))).trim_end()
+        )?;
+    }
+
+    Ok(())
+}
+
+// A memory that a scanner reads differently from the reference, made as small as possible.
+struct Failure {
+    // How many memories were tried, this one included.
+    memories: usize,
+    scanner: &'static str,
+    mode: Mode,
+    memory: Vec<u8>,
+    found: Found,
+}
+
+// Tries `iterations` random memories and returns the first one that fails.
+fn find_failure(seed: u64, iterations: usize) -> Option<Failure> {
+    let instructions = InstructionSet::standard();
+    let lexers = MODES
+        .iter()
+        .map(|mode| {
+            let grammar = if mode.lenient {
+                Grammar::Lenient
+            } else {
+                Grammar::Strict
+            };
+            Lexer::new(grammar, &instructions).expressions(mode.max_depth)
+        })
+        .collect::<Vec<Lexer>>();
+    let modes = MODES
+        .iter()
+        .zip(&lexers)
+        .map(|(&mode, lexer)| (mode, variants(lexer)))
+        .collect::<Vec<(Mode, Vec<Variant>)>>();
+    let mut rng = Rng::new(seed);
+
+    for iteration in 0..iterations {
+        let memory = random_memory(&mut rng);
+
+        let Some((mode, variant)) = modes.iter().find_map(|(mode, variants)| {
+            let expected = reference(&memory, *mode);
+            variants
+                .iter()
+                .find(|variant| (variant.scan)(&memory) != expected)
+                .map(|variant| (*mode, variant))
+        }) else {
+            continue;
+        };
+
+        let memory = minimize(&memory, |memory| {
+            (variant.scan)(memory) != reference(memory, mode)
+        });
+
+        return Some(Failure {
+            memories: iteration + 1,
+            scanner: variant.name,
+            mode,
+            found: (variant.scan)(&memory),
+            memory,
+        });
+    }
+
+    None
+}
+
+// Returns true if every variant agreed with the reference on every memory, in every mode.
+pub fn run(seed: u64, iterations: usize, corpus: &str) -> bool {
+    let Some(failure) = find_failure(seed, iterations) else {
+        println!("{iterations} memories, every scanner agrees with the reference");
+        return true;
+    };
+
+    println!(
+        "FAIL after {} memories, the {} differs from the reference ({})",
+        failure.memories, failure.scanner, failure.mode.name
+    );
+    println!("  memory:   {}", conformance::escape(&failure.memory));
+    println!(
+        "  expected: {}",
+        canonical(&reference(&failure.memory, failure.mode))
+    );
+    println!("  found:    {}", canonical(&failure.found));
+
+    match add_to_corpus(corpus, seed, &failure.memory) {
+        Ok(()) => println!("Added to {corpus}"),
+        Err(err) => eprintln!("error: cannot add to '{corpus}': {err}"),
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A short run of --fuzz, the corpus is only written by the real thing.
+    #[test]
+    fn scanners_agree_with_the_reference() {
+        if let Some(failure) = find_failure(2024, 3_000) {
+            panic!(
+                "the {} differs from the reference ({}) on {}",
+                failure.scanner,
+                failure.mode.name,
+                conformance::escape(&failure.memory)
+            );
+        }
+    }
+
+    #[test]
+    fn minimize_keeps_what_fails() {
+        let memory = b"xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+
+        let contains = |memory: &[u8]| memory.windows(8).any(|window| window == b"mul(5,5)");
+        assert_eq!(minimize(memory, contains), b"mul(5,5)");
+
+        // Two instructions are the least the reference can find two of.
+        let two = |memory: &[u8]| reference(memory, MODES[0]).len() >= 2;
+        let smallest = minimize(memory, two);
+        assert_eq!(reference(&smallest, MODES[0]).len(), 2);
+        assert_eq!(
+            smallest.len(),
+            reference(&smallest, MODES[0])
+                .iter()
+                .map(|(_, instruction)| instruction.len())
+                .sum::<usize>()
+        );
+
+        // A memory that doesn't fail can't get any smaller.
+        assert_eq!(minimize(memory, |_| false), memory);
+        assert_eq!(minimize(b"", |_| true), b"");
+        assert_eq!(minimize(memory, |_| true), b"");
+    }
+
+    // Where the lenient grammar finds what the strict one doesn't.
+    #[test]
+    fn minimize_a_difference() {
+        let memory = b"do()mul(1,2)mul( 3,4)don't()";
+        let differs = |memory: &[u8]| reference(memory, MODES[0]) != reference(memory, MODES[1]);
+
+        assert_eq!(minimize(memory, differs), b"mul( 3,4)");
+        assert_eq!(
+            canonical(&reference(memory, MODES[1])),
+            "do() mul(1,2) mul(3,4) don't()"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+// Generates corrupted memory with known answers. The memory is built from pieces: valid
+// mul(X,Y), do() and don't() instructions, near misses that must not be taken for
+// instructions (like "mul[1,2]" or "mul( 1,2)") and noise. The sums of both parts are added
+// up while the pieces are chosen, without scanning the memory, so the lexer can be checked
+// against them.
+//
+// The sums are the ones of the strict grammar with the standard instructions. Noise never
+// holds '(' or ')', so it can't turn into an instruction or complete a near miss like
+// "mul(1,2". The same seed always gives the same memory.
+
+use crate::instructions::InstructionSet;
+use crate::lexer::{Grammar, Lexer};
+use crate::program::Program;
+use crate::replay;
+
+// splitmix64, good enough to pick pieces and simple enough not to need a crate.
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // A number in 0..bound.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len() as u64) as usize]
+    }
+}
+
+pub struct Generated {
+    pub memory: Vec<u8>,
+    pub part_1: u128,
+    pub part_2: u128,
+}
+
+pub const DEFAULT_SIZE: usize = 20_000;
+
+// Printable ASCII without the parentheses, like the noise in the puzzle input.
+const NOISE: &[u8] = b" !\"#$%&'*+,-./0123456789:;<=>?@[]^_`{|}~abcdefghijklmnopqrstuvwxyz\n";
+
+// Near misses. {a} and {b} are replaced by random operands.
+const DECOYS: &[&str] = &[
+    "mul[{a},{b}]",
+    "mul({a},{b}",
+    "mul( {a},{b})",
+    "mul({a} ,{b})",
+    "mul({a},{b},{a})",
+    "mul({a}1000,{b})",
+    "mul(-{a},{b})",
+    "mul ({a},{b})",
+    "don_t()",
+    "do_not()",
+    "dont()",
+    "do( )",
+    "don't(",
+];
+
+// An operand of 1 to 3 digits, sometimes with leading zeros.
+fn operand(rng: &mut Rng) -> (u32, String) {
+    let value = match rng.below(3) {
+        0 => rng.below(10),
+        1 => rng.below(100),
+        _ => rng.below(1000),
+    } as u32;
+
+    let text = if rng.below(10) == 0 {
+        format!("{value:03}")
+    } else {
+        value.to_string()
+    };
+
+    (value, text)
+}
+
+// About `size` bytes of memory, it's only cut at the end of a piece.
+pub fn generate(seed: u64, size: usize) -> Generated {
+    let mut rng = Rng::new(seed);
+    let mut memory: Vec<u8> = vec![];
+    let (mut part_1, mut part_2) = (0u128, 0u128);
+    let mut enabled = true;
+
+    while memory.len() < size {
+        let piece = match rng.below(100) {
+            0..=44 => (0..1 + rng.below(12))
+                .map(|_| *rng.pick(NOISE) as char)
+                .collect::<String>(),
+            45..=74 => {
+                let (a, a_text) = operand(&mut rng);
+                let (b, b_text) = operand(&mut rng);
+                let value = a as u128 * b as u128;
+
+                part_1 += value;
+                if enabled {
+                    part_2 += value;
+                }
+
+                format!("mul({a_text},{b_text})")
+            }
+            75..=89 => {
+                let (_, a) = operand(&mut rng);
+                let (_, b) = operand(&mut rng);
+                rng.pick(DECOYS).replace("{a}", &a).replace("{b}", &b)
+            }
+            90..=94 => {
+                enabled = true;
+                "do()".to_string()
+            }
+            _ => {
+                enabled = false;
+                "don't()".to_string()
+            }
+        };
+
+        memory.extend_from_slice(piece.as_bytes());
+    }
+
+    Generated {
+        memory,
+        part_1,
+        part_2,
+    }
+}
+
+// What the strict lexer finds in the memory, to compare with the sums that were generated.
+// The instructions found are run like an exported program (see replay.rs).
+pub fn scanned_sums(memory: &[u8]) -> (u128, u128) {
+    let instructions = InstructionSet::standard();
+    let lexer = Lexer::new(Grammar::Strict, &instructions);
+    let program = Program::extract("standard", &instructions, &lexer.tokenize(memory));
+
+    // The program was just extracted with the same instructions, it always runs.
+    replay::run(&program).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scanned_sums_match() {
+        for seed in [0, 1, 2, 3, 42, 2024, u64::MAX] {
+            for size in [0, 1, 100, DEFAULT_SIZE] {
+                let generated = generate(seed, size);
+
+                assert!(generated.memory.len() >= size);
+                assert_eq!(
+                    scanned_sums(&generated.memory),
+                    (generated.part_1, generated.part_2),
+                    "seed {seed}, size {size}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_same_memory() {
+        for seed in [0, 7, 2024] {
+            let first = generate(seed, 5_000);
+            let second = generate(seed, 5_000);
+
+            assert_eq!(first.memory, second.memory);
+            assert_eq!((first.part_1, first.part_2), (second.part_1, second.part_2));
+        }
+
+        assert_ne!(generate(1, 5_000).memory, generate(2, 5_000).memory);
+    }
+
+    // Only the end of the memory differs between sizes.
+    #[test]
+    fn smaller_size_is_a_prefix() {
+        let short = generate(9, 1_000);
+        let long = generate(9, 10_000);
+
+        assert!(long.memory.starts_with(&short.memory));
+    }
+}
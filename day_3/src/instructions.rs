@@ -0,0 +1,126 @@
+// The instructions the lexer recognizes. Each one has a name, a number of operands and what it
+// does when the program runs. The same name can be registered with different arities, like
+// mul(a,b) and mul(a,b,c) in the extended set.
+
+use crate::lexer::Token;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Semantics {
+    // Computes a value from the operands and adds it to the sums. Part 2 only gets it while
+    // the state is enabled. The operands can be values of other instructions in expression
+    // mode (see lexer.rs), so they are u128 too. None means the value doesn't fit.
+    Value(fn(&[u128]) -> Option<u128>),
+    Enable,
+    Disable,
+    // Clears both sums and enables the state again.
+    Reset,
+}
+
+#[derive(Clone, Debug)]
+pub struct Instruction {
+    pub name: &'static str,
+    pub arity: usize,
+    pub semantics: Semantics,
+}
+
+pub struct InstructionSet {
+    instructions: Vec<Instruction>,
+}
+
+fn product(operands: &[u128]) -> Option<u128> {
+    operands
+        .iter()
+        .try_fold(1u128, |result, &operand| result.checked_mul(operand))
+}
+
+fn sum(operands: &[u128]) -> Option<u128> {
+    operands
+        .iter()
+        .try_fold(0u128, |result, &operand| result.checked_add(operand))
+}
+
+impl InstructionSet {
+    pub fn new() -> Self {
+        InstructionSet {
+            instructions: vec![],
+        }
+    }
+
+    pub fn register(
+        &mut self,
+        name: &'static str,
+        arity: usize,
+        semantics: Semantics,
+    ) -> Result<(), String> {
+        if self.find(name, arity).is_some() {
+            return Err(format!(
+                "{name} with {arity} operand(s) is already registered"
+            ));
+        }
+
+        self.instructions.push(Instruction {
+            name,
+            arity,
+            semantics,
+        });
+
+        Ok(())
+    }
+
+    // The puzzle's instructions: mul(a,b), do() and don't().
+    pub fn standard() -> Self {
+        let mut result = InstructionSet::new();
+        result
+            .register("mul", 2, Semantics::Value(product))
+            .unwrap();
+        result.register("do", 0, Semantics::Enable).unwrap();
+        result.register("don't", 0, Semantics::Disable).unwrap();
+        result
+    }
+
+    // The standard set plus add(a,b), mul(a,b,c) and reset().
+    pub fn extended() -> Self {
+        let mut result = InstructionSet::standard();
+        result.register("add", 2, Semantics::Value(sum)).unwrap();
+        result
+            .register("mul", 3, Semantics::Value(product))
+            .unwrap();
+        result.register("reset", 0, Semantics::Reset).unwrap();
+        result
+    }
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "standard" => Some(InstructionSet::standard()),
+            "extended" => Some(InstructionSet::extended()),
+            _ => None,
+        }
+    }
+
+    pub fn find(&self, name: &str, arity: usize) -> Option<&Instruction> {
+        self.instructions
+            .iter()
+            .find(|instruction| instruction.name == name && instruction.arity == arity)
+    }
+
+    // What a token found by the lexer does. The lexer only emits registered instructions, so
+    // there always is one.
+    pub fn semantics(&self, token: &Token) -> Semantics {
+        self.find(token.name, token.operands.len())
+            .unwrap()
+            .semantics
+    }
+
+    // Every registered name once, no matter how many arities it has.
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut result: Vec<&'static str> = vec![];
+
+        for instruction in &self.instructions {
+            if !result.contains(&instruction.name) {
+                result.push(instruction.name);
+            }
+        }
+
+        result
+    }
+}
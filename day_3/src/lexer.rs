@@ -0,0 +1,331 @@
+// Scans the corrupted memory once, from left to right, and emits every valid instruction
+// together with the byte range it occupies. An instruction is written as
+//
+//   name(X,Y,...)
+//
+// where the name and the number of operands must match one of the registered instructions
+// (see instructions.rs), by default mul(X,Y), do() and don't().
+//
+// The strict grammar only takes operands made of 1 to 3 digits, as the puzzle says. The lenient
+// grammar also takes a leading '+', whitespace around the operands and any number of digits,
+// as long as the value fits in a u32.
+//
+// Anything else is noise and is skipped one byte at a time, so an instruction that starts
+// inside a broken one (like the "mul(2,3)" in "mul(mul(2,3)") is still found. The memory is
+// taken as raw bytes: instructions are plain ASCII and everything around them can be any
+// byte, valid UTF-8 or not. Spans are byte offsets into the memory.
+//
+// In expression mode an operand can also be another instruction that computes a value, like
+// the mul(2,3) in "mul(mul(2,3),4)". Those are read recursively, up to a maximum depth, and
+// the whole expression is a single instruction.
+//
+// No instruction is longer than MAX_INSTRUCTION_LEN bytes. The lenient grammar has no other
+// limit on whitespace and leading zeros, and without one a single "mul(" followed by megabytes
+// of spaces would have to be kept and scanned again and again while streaming.
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::instructions::{InstructionSet, Semantics};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Grammar {
+    Strict,
+    Lenient,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Operand {
+    Literal(u32),
+    // Only in expression mode.
+    Nested(Token),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Literal(value) => write!(f, "{value}"),
+            Operand::Nested(token) => write!(f, "{token}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub name: &'static str,
+    pub operands: Vec<Operand>,
+}
+
+impl Token {
+    // The value of an instruction that computes one, the nested instructions are computed
+    // first. None if it doesn't compute a value or if the value doesn't fit in a u128.
+    pub fn evaluate(&self, instructions: &InstructionSet) -> Option<u128> {
+        let Semantics::Value(compute) =
+            instructions.find(self.name, self.operands.len())?.semantics
+        else {
+            return None;
+        };
+
+        let values = self
+            .operands
+            .iter()
+            .map(|operand| match operand {
+                Operand::Literal(value) => Some(*value as u128),
+                Operand::Nested(token) => token.evaluate(instructions),
+            })
+            .collect::<Option<Vec<u128>>>()?;
+
+        compute(&values)
+    }
+}
+
+// Prints the token the way it is written in a valid memory, like "mul(2,4)" or "do()".
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operands = self
+            .operands
+            .iter()
+            .map(|operand| operand.to_string())
+            .collect::<Vec<String>>();
+
+        write!(f, "{}({})", self.name, operands.join(","))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Spanned {
+    pub token: Token,
+    pub span: Range<usize>,
+}
+
+fn count_while(input: &[u8], predicate: impl Fn(&u8) -> bool) -> usize {
+    input.iter().take_while(|&byte| predicate(byte)).count()
+}
+
+// The value of a run of ASCII digits, unless it doesn't fit in a u32.
+fn digits_value(digits: &[u8]) -> Option<u32> {
+    digits.iter().try_fold(0u32, |value, digit| {
+        value.checked_mul(10)?.checked_add((digit - b'0') as u32)
+    })
+}
+
+// Why nothing was recognized at a position. An instruction can only be cut short when the
+// input is read in chunks (see stream.rs), a complete memory never needs more.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Miss {
+    NotFound,
+    NeedMore,
+}
+
+// How deep instructions are nested at most in expression mode, unless told otherwise.
+pub const DEFAULT_MAX_DEPTH: usize = 8;
+
+// Far more than any instruction of the puzzle needs, and enough for deeply nested expressions.
+pub const MAX_INSTRUCTION_LEN: usize = 64 * 1024;
+
+// The part of `input` an instruction starting there can take.
+fn window(input: &[u8]) -> &[u8] {
+    &input[..input.len().min(MAX_INSTRUCTION_LEN)]
+}
+
+// A parsed value and the number of bytes it took.
+type Parsed<T> = Result<(T, usize), Miss>;
+
+pub struct Lexer<'a> {
+    pub grammar: Grammar,
+    pub instructions: &'a InstructionSet,
+    // How deep instructions can be nested in operands, 0 unless in expression mode.
+    pub max_depth: usize,
+    names: Vec<&'static str>,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(grammar: Grammar, instructions: &'a InstructionSet) -> Self {
+        Lexer {
+            grammar,
+            instructions,
+            max_depth: 0,
+            names: instructions.names(),
+        }
+    }
+
+    // Turns on expression mode.
+    pub fn expressions(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    // Reads a number at the start of `input`. Running out of input anywhere means the number
+    // could still go on.
+    fn number(&self, input: &[u8]) -> Parsed<u32> {
+        let lenient = self.grammar == Grammar::Lenient;
+        let mut pos = 0;
+
+        if lenient {
+            pos += count_while(input, u8::is_ascii_whitespace);
+            if input[pos..].starts_with(b"+") {
+                pos += 1;
+            }
+        }
+
+        let len = count_while(&input[pos..], u8::is_ascii_digit);
+        if !lenient && len > 3 {
+            return Err(Miss::NotFound);
+        }
+
+        if pos + len == input.len() {
+            return Err(Miss::NeedMore);
+        }
+
+        if len == 0 {
+            return Err(Miss::NotFound);
+        }
+
+        let value = digits_value(&input[pos..pos + len]).ok_or(Miss::NotFound)?;
+        pos += len;
+
+        if lenient {
+            pos += count_while(&input[pos..], u8::is_ascii_whitespace);
+            if pos == input.len() {
+                return Err(Miss::NeedMore);
+            }
+        }
+
+        Ok((value, pos))
+    }
+
+    // Reads a number, or in expression mode an instruction that computes a value, at the
+    // start of `input`. `depth` is the depth of the instruction the operand belongs to.
+    fn operand(&self, input: &[u8], depth: usize) -> Parsed<Operand> {
+        match self.number(input) {
+            Ok((value, len)) => return Ok((Operand::Literal(value), len)),
+            Err(Miss::NotFound) if depth < self.max_depth => {}
+            Err(miss) => return Err(miss),
+        }
+
+        let lenient = self.grammar == Grammar::Lenient;
+        let mut pos = 0;
+
+        if lenient {
+            pos += count_while(input, u8::is_ascii_whitespace);
+        }
+
+        let (token, len) = self.token(&input[pos..], depth + 1)?;
+        pos += len;
+
+        if !matches!(self.instructions.semantics(&token), Semantics::Value(_)) {
+            return Err(Miss::NotFound);
+        }
+
+        if lenient {
+            pos += count_while(&input[pos..], u8::is_ascii_whitespace);
+        }
+
+        if pos == input.len() {
+            return Err(Miss::NeedMore);
+        }
+
+        Ok((Operand::Nested(token), pos))
+    }
+
+    // Reads "X,Y,...)" or just ")" at the start of `input`, returns the operands and the
+    // number of bytes they took, including the closing parenthesis.
+    fn operands(&self, input: &[u8], depth: usize) -> Parsed<Vec<Operand>> {
+        let mut result: Vec<Operand> = vec![];
+        let mut pos = 0;
+
+        match input.first() {
+            None => return Err(Miss::NeedMore),
+            Some(b')') => return Ok((result, 1)),
+            Some(_) => {}
+        }
+
+        loop {
+            // operand() never stops at the end of the input, so there is a next byte.
+            let (value, len) = self.operand(&input[pos..], depth)?;
+            result.push(value);
+            pos += len;
+
+            match input[pos] {
+                b',' => pos += 1,
+                b')' => return Ok((result, pos + 1)),
+                _ => return Err(Miss::NotFound),
+            }
+        }
+    }
+
+    // Recognizes the instruction starting at the beginning of `input`, if there is one.
+    // `depth` is how deep it is nested in the operands of another one.
+    fn token(&self, input: &[u8], depth: usize) -> Parsed<Token> {
+        let mut miss = Miss::NotFound;
+
+        for &name in &self.names {
+            let opening = name.len() + 1;
+
+            // The input ends inside something that could still become "name(".
+            if input.len() < opening {
+                if name.as_bytes().starts_with(input) {
+                    miss = Miss::NeedMore;
+                }
+                continue;
+            }
+
+            if !input.starts_with(name.as_bytes()) || input[name.len()] != b'(' {
+                continue;
+            }
+
+            let (operands, len) = self.operands(&input[opening..], depth)?;
+            if self.instructions.find(name, operands.len()).is_none() {
+                return Err(Miss::NotFound);
+            }
+
+            return Ok((Token { name, operands }, opening + len));
+        }
+
+        Err(miss)
+    }
+
+    // Recognizes the instruction at the start of a complete `input`, with the number of bytes
+    // it takes. Used by the fast scanner (see fast.rs) to check the candidates it finds.
+    pub fn recognize(&self, input: &[u8]) -> Option<(Token, usize)> {
+        self.token(window(input), 0).ok()
+    }
+
+    // Emits every instruction in `memory`, with spans shifted by `offset`. Unless `at_end` is
+    // set, scanning stops at the first instruction that might go on past the end of `memory`.
+    // Returns how many bytes were scanned, the rest has to be scanned again with more input.
+    pub fn scan(
+        &self,
+        memory: &[u8],
+        offset: usize,
+        at_end: bool,
+        emit: &mut impl FnMut(Spanned),
+    ) -> usize {
+        let mut pos = 0;
+
+        while pos < memory.len() {
+            let window = window(&memory[pos..]);
+
+            match self.token(window, 0) {
+                Ok((token, len)) => {
+                    emit(Spanned {
+                        token,
+                        span: offset + pos..offset + pos + len,
+                    });
+                    pos += len;
+                }
+                // Past MAX_INSTRUCTION_LEN more input can't help anymore.
+                Err(Miss::NeedMore) if !at_end && window.len() < MAX_INSTRUCTION_LEN => return pos,
+                Err(_) => pos += 1,
+            }
+        }
+
+        pos
+    }
+
+    pub fn tokenize(&self, memory: &[u8]) -> Vec<Spanned> {
+        let mut result: Vec<Spanned> = vec![];
+        self.scan(memory, 0, true, &mut |spanned| result.push(spanned));
+        result
+    }
+}
@@ -0,0 +1,699 @@
+// https://adventofcode.com/2024/day/3
+//
+// The memory is tokenized once, both parts are computed from the same token stream. Part 1
+// sums every mul(X,Y), part 2 only the ones that aren't disabled by a don't().
+//
+// Flags:
+//
+//   --lenient              also accept signs, whitespace and long numbers as operands
+//   --instructions <set>   "standard" (mul, do, don't) or "extended" (also add(a,b),
+//                          mul(a,b,c) and reset())
+//   --expressions [depth]  also accept instructions that compute a value as operands, like
+//                          mul(mul(2,3),4), nested up to the given depth (8 by default)
+//   --stream               read the memory in chunks instead of all at once
+//   --chunk-size <bytes>   size of the chunks read by --stream
+//   --timeline             also print every do() and don't() with its offset, and the enabled
+//                          and disabled parts of the memory with how many values they hold
+//   --fast                 find the instructions with the fast scanner instead of the lexer
+//   --bench [megabytes]    time the lexer against the fast scanner on input.txt repeated to
+//                          the given size instead
+//   --annotate [file]      print the memory with the instructions highlighted (only in a
+//                          terminal), or write it as an HTML page to the given file
+//   --export <file>        write the instructions found to a file instead: JSON for ".json",
+//                          the instructions as text for ".txt" and bytecode for anything else
+//   --replay <file>        compute the sums from an exported JSON or bytecode program instead
+//                          of the memory
+//   --generate <file>      write random corrupted memory to a file instead and print its sums,
+//                          checked against the lexer
+//   --fuzz [iterations]    compare every scanner with a reference matcher on random memories
+//                          instead, failures are minimized and added to conformance.txt
+//   --seed <n>             seed of --generate and --fuzz, a new one every run by default
+//   --size <bytes>         about how big the memory of --generate is, 20000 by default
+//   --conformance [path]   check both grammars against a corpus of tricky cases instead,
+//                          defaults to conformance.txt
+
+mod annotate;
+mod bench;
+mod conformance;
+mod fast;
+mod fuzz;
+mod generate;
+mod instructions;
+mod lexer;
+mod program;
+mod replay;
+mod stream;
+
+use std::io::IsTerminal;
+
+use instructions::{InstructionSet, Semantics};
+use lexer::{Grammar, Lexer, Spanned};
+
+// Corrupted memory can hold any byte, so it isn't read as text.
+fn read_input() -> Vec<u8> {
+    std::fs::read("input.txt").unwrap()
+}
+
+mod util {
+    use std::ops::Range;
+
+    use crate::instructions::Semantics;
+
+    // A do(), don't() or reset() and where it is in the memory. Toggles that don't change
+    // anything, like a do() while already enabled, are kept too.
+    pub struct Toggle {
+        pub offset: usize,
+        pub semantics: Semantics,
+        // The state after the toggle, and whether it was different before.
+        pub enabled: bool,
+        pub changed: bool,
+    }
+
+    // A part of the memory where the state stays the same. In the standard instruction set
+    // the values are the mul()s.
+    pub struct Interval {
+        pub span: Range<usize>,
+        pub enabled: bool,
+        pub values: usize,
+    }
+
+    // The sums of both parts. A value that would take either sum past what a u128 holds is left
+    // out of both, like a value that doesn't fit in a u128 itself.
+    #[derive(Default)]
+    pub struct Sums {
+        pub all: u128,
+        pub enabled: u128,
+    }
+
+    impl Sums {
+        // Returns false if the value was left out.
+        pub fn add(&mut self, value: u128, enabled: bool) -> bool {
+            let all = self.all.checked_add(value);
+            let part_2 = if enabled {
+                self.enabled.checked_add(value)
+            } else {
+                Some(self.enabled)
+            };
+
+            match (all, part_2) {
+                (Some(all), Some(part_2)) => {
+                    (self.all, self.enabled) = (all, part_2);
+                    true
+                }
+                _ => false,
+            }
+        }
+
+        pub fn reset(&mut self) {
+            *self = Sums::default();
+        }
+    }
+
+    pub struct DoDontState {
+        enabled: bool,
+        // The toggles and intervals are only kept for --timeline, there can be as many as there
+        // are instructions and --stream must not hold on to every one of them.
+        recording: bool,
+        toggles: Vec<Toggle>,
+        // Only the starts are known while the memory is scanned, the spans are empty.
+        intervals: Vec<Interval>,
+    }
+
+    impl DoDontState {
+        pub fn new() -> Self {
+            // Default state is enabled = true.
+            DoDontState {
+                enabled: true,
+                recording: false,
+                toggles: vec![],
+                intervals: vec![Interval {
+                    span: 0..0,
+                    enabled: true,
+                    values: 0,
+                }],
+            }
+        }
+
+        // Also keeps every toggle and interval, for toggles() and intervals().
+        pub fn with_timeline() -> Self {
+            DoDontState {
+                recording: true,
+                ..DoDontState::new()
+            }
+        }
+
+        // do() enables instructions that compute a value and don't() disables them. `offset`
+        // is where the instruction starts in the memory.
+        pub fn apply(&mut self, semantics: &Semantics, offset: usize) {
+            let enabled = match semantics {
+                Semantics::Enable | Semantics::Reset => true,
+                Semantics::Disable => false,
+                Semantics::Value(_) => {
+                    if self.recording {
+                        self.intervals.last_mut().unwrap().values += 1;
+                    }
+                    return;
+                }
+            };
+
+            let changed = enabled != self.enabled;
+            self.enabled = enabled;
+
+            if !self.recording {
+                return;
+            }
+
+            self.toggles.push(Toggle {
+                offset,
+                semantics: *semantics,
+                enabled,
+                changed,
+            });
+
+            // The toggle itself belongs to the interval it starts.
+            if changed {
+                self.intervals.push(Interval {
+                    span: offset..offset,
+                    enabled,
+                    values: 0,
+                });
+            }
+        }
+
+        pub fn is_enabled(&self) -> bool {
+            self.enabled
+        }
+
+        // Empty unless the state was made with_timeline(), like intervals().
+        pub fn toggles(&self) -> &[Toggle] {
+            &self.toggles
+        }
+
+        // Every interval, each one ends where the next one starts and the last one at
+        // `memory_len`.
+        pub fn intervals(&self, memory_len: usize) -> Vec<Interval> {
+            let ends = self
+                .intervals
+                .iter()
+                .skip(1)
+                .map(|next| next.span.start)
+                .chain(std::iter::once(memory_len));
+
+            self.intervals
+                .iter()
+                .zip(ends)
+                .map(|(interval, end)| Interval {
+                    span: interval.span.start..end,
+                    enabled: interval.enabled,
+                    values: interval.values,
+                })
+                .collect()
+        }
+    }
+}
+
+// Runs the instructions one at a time and keeps the sums of both parts.
+struct Machine<'a> {
+    instructions: &'a InstructionSet,
+    state: util::DoDontState,
+    sums: util::Sums,
+}
+
+impl<'a> Machine<'a> {
+    // `timeline` keeps what --timeline prints, see util::DoDontState.
+    fn new(instructions: &'a InstructionSet, timeline: bool) -> Self {
+        Machine {
+            instructions,
+            state: if timeline {
+                util::DoDontState::with_timeline()
+            } else {
+                util::DoDontState::new()
+            },
+            sums: util::Sums::default(),
+        }
+    }
+
+    fn step(&mut self, Spanned { token, span }: &Spanned) {
+        let semantics = self.instructions.semantics(token);
+
+        self.state.apply(&semantics, span.start);
+
+        match semantics {
+            Semantics::Value(_) => {
+                // Nested instructions make it possible to go past what a u128 holds.
+                let Some(value) = token.evaluate(self.instructions) else {
+                    println!("{token} at {span:?} (too large, skipped)");
+                    return;
+                };
+
+                if !self.sums.add(value, self.state.is_enabled()) {
+                    println!("{token} at {span:?} (sum too large, skipped)");
+                } else if self.state.is_enabled() {
+                    println!("{token} at {span:?}");
+                } else {
+                    println!("{token} at {span:?} (ignored)");
+                }
+            }
+            Semantics::Reset => {
+                self.sums.reset();
+                println!("{token} at {span:?}");
+            }
+            Semantics::Enable | Semantics::Disable => {}
+        }
+    }
+}
+
+// Every do(), don't() and reset() in the memory, then the parts of the memory they split it in.
+fn print_timeline(state: &util::DoDontState, memory_len: usize) {
+    println!("Toggles:");
+    for toggle in state.toggles() {
+        let name = match toggle.semantics {
+            Semantics::Enable => "do()",
+            Semantics::Disable => "don't()",
+            Semantics::Reset => "reset()",
+            Semantics::Value(_) => unreachable!(),
+        };
+
+        let effect = match (toggle.changed, toggle.enabled) {
+            (false, _) => "no change",
+            (true, true) => "enables",
+            (true, false) => "disables",
+        };
+
+        println!("  {name:8} at {:>6}, {effect}", toggle.offset);
+    }
+
+    println!("Intervals:");
+    for interval in state.intervals(memory_len) {
+        println!(
+            "  {:>15} {:8}, {} value instruction(s)",
+            format!("{:?}", interval.span),
+            if interval.enabled {
+                "enabled"
+            } else {
+                "disabled"
+            },
+            interval.values
+        );
+    }
+}
+
+fn has_flag(args: &[String], name: &str) -> bool {
+    args.iter().any(|arg| arg == name)
+}
+
+// The value after a flag, unless the next argument is another flag.
+fn flag_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    let index = args.iter().position(|arg| arg == name)?;
+    args.get(index + 1)
+        .map(|value| value.as_str())
+        .filter(|value| !value.starts_with("--"))
+}
+
+// The seed given with --seed, or one that's different every run.
+fn parse_seed(args: &[String]) -> u64 {
+    match flag_value(args, "--seed") {
+        Some(seed) => seed.parse::<u64>().unwrap_or_else(|_| {
+            eprintln!("error: '--seed' expects a number");
+            std::process::exit(2);
+        }),
+        None => std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64,
+    }
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+
+    if has_flag(&args, "--conformance") {
+        let path = flag_value(&args, "--conformance").unwrap_or("conformance.txt");
+
+        match conformance::run(path) {
+            Ok(true) => return,
+            Ok(false) => std::process::exit(1),
+            Err(err) => {
+                eprintln!("error: {err}");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    if has_flag(&args, "--fuzz") {
+        let iterations = match flag_value(&args, "--fuzz") {
+            Some(iterations) => iterations.parse::<usize>().unwrap_or_else(|_| {
+                eprintln!("error: '--fuzz' expects a number of iterations");
+                std::process::exit(2);
+            }),
+            None => fuzz::DEFAULT_ITERATIONS,
+        };
+
+        let seed = parse_seed(&args);
+        println!("Seed: {seed}");
+
+        if !fuzz::run(seed, iterations, "conformance.txt") {
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
+    if has_flag(&args, "--generate") {
+        let Some(path) = flag_value(&args, "--generate") else {
+            eprintln!("error: '--generate' expects a file");
+            std::process::exit(2);
+        };
+
+        let seed = parse_seed(&args);
+
+        let size = match flag_value(&args, "--size") {
+            Some(size) => size.parse::<usize>().unwrap_or_else(|_| {
+                eprintln!("error: '--size' expects a number of bytes");
+                std::process::exit(2);
+            }),
+            None => generate::DEFAULT_SIZE,
+        };
+
+        let generated = generate::generate(seed, size);
+        if let Err(err) = std::fs::write(path, &generated.memory) {
+            eprintln!("error: cannot write '{path}': {err}");
+            std::process::exit(1);
+        }
+
+        println!("Seed: {seed}");
+        println!("Size: {} bytes", generated.memory.len());
+        println!("Part 1 sum: {}", generated.part_1);
+        println!("Part 2 sum: {}", generated.part_2);
+
+        let scanned = generate::scanned_sums(&generated.memory);
+        if scanned != (generated.part_1, generated.part_2) {
+            println!("FAIL: the lexer finds {} and {}", scanned.0, scanned.1);
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
+    let grammar = if has_flag(&args, "--lenient") {
+        Grammar::Lenient
+    } else {
+        Grammar::Strict
+    };
+
+    if has_flag(&args, "--replay") {
+        let Some(path) = flag_value(&args, "--replay") else {
+            eprintln!("error: '--replay' expects a file");
+            std::process::exit(2);
+        };
+
+        let result = std::fs::read(path)
+            .map_err(|err| format!("cannot read '{path}': {err}"))
+            .and_then(|bytes| program::Program::load(&bytes))
+            .and_then(|program| replay::run(&program));
+
+        match result {
+            Ok((part_1, part_2)) => {
+                println!("Part 1 sum: {part_1}");
+                println!("Part 2 sum: {part_2}");
+                return;
+            }
+            Err(err) => {
+                eprintln!("error: {err}");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let set_name = flag_value(&args, "--instructions").unwrap_or("standard");
+    let instructions = InstructionSet::by_name(set_name).unwrap_or_else(|| {
+        eprintln!("error: unknown instruction set '{set_name}', expected standard or extended");
+        std::process::exit(2);
+    });
+
+    let mut lexer = Lexer::new(grammar, &instructions);
+
+    if has_flag(&args, "--expressions") {
+        let max_depth = match flag_value(&args, "--expressions") {
+            Some(depth) => depth
+                .parse::<usize>()
+                .ok()
+                .filter(|&depth| depth > 0)
+                .unwrap_or_else(|| {
+                    eprintln!("error: '--expressions' expects a positive depth");
+                    std::process::exit(2);
+                }),
+            None => lexer::DEFAULT_MAX_DEPTH,
+        };
+
+        lexer = lexer.expressions(max_depth);
+    }
+
+    if has_flag(&args, "--bench") {
+        let megabytes = match flag_value(&args, "--bench") {
+            Some(size) => size
+                .parse::<usize>()
+                .ok()
+                .filter(|&size| size > 0)
+                .unwrap_or_else(|| {
+                    eprintln!("error: '--bench' expects a positive number of megabytes");
+                    std::process::exit(2);
+                }),
+            None => bench::DEFAULT_MEGABYTES,
+        };
+
+        if !bench::run(&lexer, &read_input(), megabytes) {
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
+    if has_flag(&args, "--export") {
+        let Some(path) = flag_value(&args, "--export") else {
+            eprintln!("error: '--export' expects a file");
+            std::process::exit(2);
+        };
+
+        let tokens = lexer.tokenize(&read_input());
+        let program = program::Program::extract(set_name, &instructions, &tokens);
+
+        let content = if path.ends_with(".json") {
+            program.to_json().into_bytes()
+        } else if path.ends_with(".txt") {
+            program.to_text().into_bytes()
+        } else {
+            program.to_bytecode()
+        };
+
+        if let Err(err) = std::fs::write(path, content) {
+            eprintln!("error: cannot write '{path}': {err}");
+            std::process::exit(1);
+        }
+
+        println!("Exported {} instruction(s) to {path}", program.ops.len());
+        return;
+    }
+
+    if has_flag(&args, "--annotate") {
+        let memory = read_input();
+        let tokens = lexer.tokenize(&memory);
+
+        match flag_value(&args, "--annotate") {
+            Some(path) => {
+                let page = annotate::html(&memory, &tokens, &instructions);
+                if let Err(err) = std::fs::write(path, page) {
+                    eprintln!("error: cannot write '{path}': {err}");
+                    std::process::exit(1);
+                }
+            }
+            None => {
+                // Escape codes only make sense in a terminal, not in a file or a pipe.
+                let color = std::io::stdout().is_terminal();
+                println!("{}", annotate::ansi(&memory, &tokens, &instructions, color));
+            }
+        }
+
+        return;
+    }
+
+    let timeline = has_flag(&args, "--timeline");
+    let mut machine = Machine::new(&instructions, timeline);
+
+    let memory_len = if has_flag(&args, "--stream") {
+        let chunk_size = match flag_value(&args, "--chunk-size") {
+            Some(size) => size
+                .parse::<usize>()
+                .ok()
+                .filter(|&size| size > 0)
+                .unwrap_or_else(|| {
+                    eprintln!("error: '--chunk-size' expects a positive number of bytes");
+                    std::process::exit(2);
+                }),
+            None => stream::DEFAULT_CHUNK_SIZE,
+        };
+
+        let file = std::fs::File::open("input.txt").unwrap();
+        let memory_len = file.metadata().unwrap().len() as usize;
+        let reader = std::io::BufReader::new(file);
+
+        if let Err(err) = stream::scan(&lexer, reader, chunk_size, |spanned| machine.step(&spanned))
+        {
+            eprintln!("error: cannot read input.txt: {err}");
+            std::process::exit(1);
+        }
+
+        memory_len
+    } else {
+        let input = read_input();
+        let tokens = if has_flag(&args, "--fast") {
+            fast::FastScanner::new(&lexer).tokenize(&input)
+        } else {
+            lexer.tokenize(&input)
+        };
+
+        for spanned in tokens {
+            machine.step(&spanned);
+        }
+
+        input.len()
+    };
+
+    if timeline {
+        print_timeline(&machine.state, memory_len);
+    }
+
+    let (part_1, part_2) = (machine.sums.all, machine.sums.enabled);
+
+    println!("Part 1 sum: {part_1}");
+    println!("Part 2 sum: {part_2}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs every instruction the strict grammar finds in the memory.
+    fn run<'a>(memory: &[u8], instructions: &'a InstructionSet, timeline: bool) -> Machine<'a> {
+        let mut machine = Machine::new(instructions, timeline);
+
+        for spanned in Lexer::new(Grammar::Strict, instructions).tokenize(memory) {
+            machine.step(&spanned);
+        }
+
+        machine
+    }
+
+    fn sums(memory: &[u8], instructions: &InstructionSet) -> (u128, u128) {
+        let machine = run(memory, instructions, false);
+        (machine.sums.all, machine.sums.enabled)
+    }
+
+    #[test]
+    fn extended_values() {
+        let extended = InstructionSet::extended();
+
+        assert_eq!(sums(b"add(4,5)mul(2,3,4)", &extended), (33, 33));
+        assert_eq!(
+            sums(b"mul(2,3)add(4,5)don't()mul(1,2,3)add(1,1)", &extended),
+            (23, 15)
+        );
+        // Only mul(a,b) is a standard instruction.
+        assert_eq!(
+            sums(
+                b"mul(2,3)add(4,5)don't()mul(1,2,3)add(1,1)",
+                &InstructionSet::standard()
+            ),
+            (6, 6)
+        );
+    }
+
+    #[test]
+    fn reset_clears_both_sums() {
+        let extended = InstructionSet::extended();
+
+        assert_eq!(sums(b"mul(2,3)don't()add(4,5)reset()", &extended), (0, 0));
+        // reset() also enables again.
+        assert_eq!(
+            sums(
+                b"mul(2,3)don't()add(4,5)reset()mul(1,2,3)add(10,20)",
+                &extended
+            ),
+            (36, 36)
+        );
+        assert_eq!(
+            sums(b"mul(9,9)reset()don't()add(1,2)do()mul(2,2,2)", &extended),
+            (11, 8)
+        );
+    }
+
+    // The offset, the state after the toggle and whether it changed.
+    fn toggles(state: &util::DoDontState) -> Vec<(usize, bool, bool)> {
+        state
+            .toggles()
+            .iter()
+            .map(|toggle| (toggle.offset, toggle.enabled, toggle.changed))
+            .collect()
+    }
+
+    fn intervals(
+        state: &util::DoDontState,
+        memory_len: usize,
+    ) -> Vec<(std::ops::Range<usize>, bool, usize)> {
+        state
+            .intervals(memory_len)
+            .into_iter()
+            .map(|interval| (interval.span, interval.enabled, interval.values))
+            .collect()
+    }
+
+    #[test]
+    fn part_2_timeline() {
+        let memory = b"xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+        let instructions = InstructionSet::standard();
+        let machine = run(memory, &instructions, true);
+
+        assert_eq!(
+            toggles(&machine.state),
+            [(20, false, true), (59, true, true)]
+        );
+        assert_eq!(
+            intervals(&machine.state, memory.len()),
+            [(0..20, true, 1), (20..59, false, 2), (59..73, true, 1)]
+        );
+        assert_eq!((machine.sums.all, machine.sums.enabled), (161, 48));
+    }
+
+    #[test]
+    fn toggles_without_change() {
+        let memory = b"do()mul(1,2)don't()don't()mul(3,4)do()";
+        let instructions = InstructionSet::standard();
+        let machine = run(memory, &instructions, true);
+
+        assert_eq!(
+            toggles(&machine.state),
+            [
+                (0, true, false),
+                (12, false, true),
+                (19, false, false),
+                (34, true, true)
+            ]
+        );
+        assert_eq!(
+            intervals(&machine.state, memory.len()),
+            [(0..12, true, 1), (12..34, false, 1), (34..38, true, 0)]
+        );
+    }
+
+    // Without --timeline nothing is kept.
+    #[test]
+    fn no_timeline() {
+        let memory = b"mul(1,2)don't()mul(3,4)do()";
+        let instructions = InstructionSet::standard();
+        let machine = run(memory, &instructions, false);
+
+        assert!(machine.state.toggles().is_empty());
+        assert_eq!(intervals(&machine.state, memory.len()), [(0..27, true, 0)]);
+        assert!(machine.state.is_enabled());
+    }
+}
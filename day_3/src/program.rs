@@ -0,0 +1,411 @@
+// The instructions found in the memory, taken out of it: a program that can be saved and run
+// again later without scanning the memory (see replay.rs). Every instruction keeps the byte
+// range it came from and whether it was enabled when it ran.
+//
+// A program can be written in three ways:
+//
+//   JSON       the whole program, easy to read and to use from other tools
+//   bytecode   the same thing, packed (see below)
+//   text       only the instructions, one per line, written the way a valid memory has them.
+//              Scanning this text finds the same instructions again, so it's also a memory.
+
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+use crate::instructions::InstructionSet;
+use crate::lexer::{self, Spanned};
+use crate::util::DoDontState;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Op {
+    pub start: usize,
+    pub end: usize,
+    // do() and don't() have the state they switch to, like in annotate mode.
+    pub enabled: bool,
+    pub name: String,
+    pub operands: Vec<Operand>,
+}
+
+// A number, or an instruction that computes a value in expression mode. In JSON a number is
+// just the number and an instruction has a name and operands, like an Op.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Operand {
+    Literal(u32),
+    Nested {
+        name: String,
+        operands: Vec<Operand>,
+    },
+}
+
+impl Operand {
+    fn from_lexer(operand: &lexer::Operand) -> Self {
+        match operand {
+            lexer::Operand::Literal(value) => Operand::Literal(*value),
+            lexer::Operand::Nested(token) => Operand::Nested {
+                name: token.name.to_string(),
+                operands: token.operands.iter().map(Operand::from_lexer).collect(),
+            },
+        }
+    }
+}
+
+fn write_call(f: &mut fmt::Formatter, name: &str, operands: &[Operand]) -> fmt::Result {
+    let operands = operands
+        .iter()
+        .map(|operand| operand.to_string())
+        .collect::<Vec<String>>();
+
+    write!(f, "{name}({})", operands.join(","))
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Literal(value) => write!(f, "{value}"),
+            Operand::Nested { name, operands } => write_call(f, name, operands),
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_call(f, &self.name, &self.operands)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Program {
+    // The instruction set the program was found with, "standard" or "extended".
+    pub instructions: String,
+    pub ops: Vec<Op>,
+}
+
+impl Program {
+    pub fn extract(set_name: &str, instructions: &InstructionSet, tokens: &[Spanned]) -> Self {
+        let mut state = DoDontState::new();
+        let mut ops: Vec<Op> = vec![];
+
+        for Spanned { token, span } in tokens {
+            let semantics = instructions.semantics(token);
+
+            state.apply(&semantics, span.start);
+            ops.push(Op {
+                start: span.start,
+                end: span.end,
+                enabled: state.is_enabled(),
+                name: token.name.to_string(),
+                operands: token.operands.iter().map(Operand::from_lexer).collect(),
+            });
+        }
+
+        Program {
+            instructions: set_name.to_string(),
+            ops,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    pub fn to_text(&self) -> String {
+        self.ops.iter().map(|op| format!("{op}\n")).collect()
+    }
+
+    // The bytecode starts with MAGIC, the name of the instruction set and a table with the
+    // names of the instructions. Then for every instruction:
+    //
+    //   opcode     index in the name table, shifted left once, the lowest bit is the state
+    //   start      distance from the end of the previous instruction
+    //   length     number of bytes in the memory
+    //   operands   how many, then each operand
+    //
+    // An operand is a number shifted left once. If the lowest bit is set it's a nested
+    // instruction instead: the index of its name, then its own operands.
+    //
+    // Every number is a LEB128 varint, so small numbers take a single byte. Names are a varint
+    // length followed by the bytes.
+    pub fn to_bytecode(&self) -> Vec<u8> {
+        let mut names: Vec<&str> = vec![];
+        for op in &self.ops {
+            collect_names(&mut names, &op.name, &op.operands);
+        }
+
+        let mut result = MAGIC.to_vec();
+        write_str(&mut result, &self.instructions);
+        write_varint(&mut result, names.len() as u64);
+        for name in &names {
+            write_str(&mut result, name);
+        }
+
+        let mut previous_end = 0;
+        for op in &self.ops {
+            let index = names.iter().position(|&name| name == op.name).unwrap();
+            write_varint(&mut result, (index as u64) << 1 | op.enabled as u64);
+            write_varint(&mut result, (op.start - previous_end) as u64);
+            write_varint(&mut result, (op.end - op.start) as u64);
+            write_operands(&mut result, &names, &op.operands);
+            previous_end = op.end;
+        }
+
+        result
+    }
+
+    pub fn from_bytecode(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader {
+            bytes: bytes
+                .strip_prefix(MAGIC)
+                .ok_or("not a Day 3 bytecode file")?,
+        };
+
+        let instructions = reader.str()?;
+        let names = (0..reader.varint()?)
+            .map(|_| reader.str())
+            .collect::<Result<Vec<String>, String>>()?;
+
+        let mut ops: Vec<Op> = vec![];
+        let mut previous_end = 0usize;
+
+        while !reader.bytes.is_empty() {
+            let opcode = reader.varint()?;
+            let name = names
+                .get((opcode >> 1) as usize)
+                .ok_or(format!("unknown opcode {opcode}"))?;
+            let start = previous_end
+                .checked_add(reader.offset()?)
+                .ok_or("offset is too large")?;
+            let end = start
+                .checked_add(reader.offset()?)
+                .ok_or("offset is too large")?;
+            let operands = reader.operands(&names, 0)?;
+
+            ops.push(Op {
+                start,
+                end,
+                enabled: opcode & 1 == 1,
+                name: name.clone(),
+                operands,
+            });
+            previous_end = end;
+        }
+
+        Ok(Program { instructions, ops })
+    }
+
+    // Tells the formats apart by their first bytes.
+    pub fn load(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.starts_with(MAGIC) {
+            Program::from_bytecode(bytes)
+        } else {
+            serde_json::from_slice(bytes).map_err(|err| format!("invalid program: {err}"))
+        }
+    }
+}
+
+const MAGIC: &[u8] = b"D3BC\x01";
+
+// Nested instructions deeper than this are taken as a broken file.
+const MAX_DEPTH: usize = 64;
+
+fn collect_names<'a>(names: &mut Vec<&'a str>, name: &'a str, operands: &'a [Operand]) {
+    if !names.contains(&name) {
+        names.push(name);
+    }
+
+    for operand in operands {
+        if let Operand::Nested { name, operands } = operand {
+            collect_names(names, name, operands);
+        }
+    }
+}
+
+fn write_operands(result: &mut Vec<u8>, names: &[&str], operands: &[Operand]) {
+    write_varint(result, operands.len() as u64);
+
+    for operand in operands {
+        match operand {
+            Operand::Literal(value) => write_varint(result, (*value as u64) << 1),
+            Operand::Nested { name, operands } => {
+                let index = names.iter().position(|other| other == name).unwrap();
+                write_varint(result, (index as u64) << 1 | 1);
+                write_operands(result, names, operands);
+            }
+        }
+    }
+}
+
+fn write_varint(result: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        result.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    result.push(value as u8);
+}
+
+fn write_str(result: &mut Vec<u8>, text: &str) {
+    write_varint(result, text.len() as u64);
+    result.extend_from_slice(text.as_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self.bytes.split_first().ok_or("truncated bytecode")?;
+            self.bytes = rest;
+            value |= ((byte & 0x7f) as u64) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err("varint is too long".to_string())
+    }
+
+    fn operands(&mut self, names: &[String], depth: usize) -> Result<Vec<Operand>, String> {
+        if depth > MAX_DEPTH {
+            return Err("instructions are nested too deep".to_string());
+        }
+
+        (0..self.varint()?)
+            .map(|_| {
+                let value = self.varint()?;
+
+                if value & 1 == 0 {
+                    let literal = u32::try_from(value >> 1)
+                        .map_err(|_| format!("operand {} is too large", value >> 1))?;
+                    return Ok(Operand::Literal(literal));
+                }
+
+                let name = names
+                    .get((value >> 1) as usize)
+                    .ok_or(format!("unknown opcode {value}"))?;
+
+                Ok(Operand::Nested {
+                    name: name.clone(),
+                    operands: self.operands(names, depth + 1)?,
+                })
+            })
+            .collect()
+    }
+
+    fn offset(&mut self) -> Result<usize, String> {
+        let value = self.varint()?;
+        usize::try_from(value).map_err(|_| format!("offset {value} is too large"))
+    }
+
+    fn str(&mut self) -> Result<String, String> {
+        let len = self.offset()?;
+        if len > self.bytes.len() {
+            return Err("truncated bytecode".to_string());
+        }
+
+        let (text, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        String::from_utf8(text.to_vec()).map_err(|_| "name is not valid UTF-8".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{Grammar, Lexer, DEFAULT_MAX_DEPTH};
+
+    fn extract(memory: &[u8], set_name: &str) -> Program {
+        let instructions = InstructionSet::by_name(set_name).unwrap();
+        let lexer = Lexer::new(Grammar::Strict, &instructions).expressions(DEFAULT_MAX_DEPTH);
+        Program::extract(set_name, &instructions, &lexer.tokenize(memory))
+    }
+
+    fn nested() -> Program {
+        extract(
+            b"xmul(2,4)don't()mul(mul(2,3),add(1,mul(4,5,6)))do()reset()mul(999,0)",
+            "extended",
+        )
+    }
+
+    #[test]
+    fn extract_keeps_spans_and_state() {
+        let program = nested();
+        let ops = program
+            .ops
+            .iter()
+            .map(|op| (op.start, op.end, op.enabled, op.to_string()))
+            .collect::<Vec<(usize, usize, bool, String)>>();
+
+        assert_eq!(
+            ops,
+            [
+                (1, 9, true, "mul(2,4)".to_string()),
+                (9, 16, false, "don't()".to_string()),
+                (16, 47, false, "mul(mul(2,3),add(1,mul(4,5,6)))".to_string()),
+                (47, 51, true, "do()".to_string()),
+                (51, 58, true, "reset()".to_string()),
+                (58, 68, true, "mul(999,0)".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn bytecode_round_trip() {
+        for program in [
+            nested(),
+            extract(b"", "standard"),
+            extract(b"mul(1,2)", "standard"),
+        ] {
+            assert_eq!(Program::load(&program.to_bytecode()), Ok(program));
+        }
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let program = nested();
+
+        assert_eq!(Program::load(program.to_json().as_bytes()), Ok(program));
+    }
+
+    // Scanning the text finds the same instructions, only at other offsets.
+    #[test]
+    fn text_is_a_memory() {
+        let program = nested();
+        let again = extract(program.to_text().as_bytes(), "extended");
+
+        assert_eq!(again.to_text(), program.to_text());
+    }
+
+    #[test]
+    fn broken_bytecode() {
+        let bytecode = nested().to_bytecode();
+
+        for len in MAGIC.len()..bytecode.len() {
+            // Cutting the file at the end of an instruction leaves a shorter valid program.
+            if let Err(err) = Program::load(&bytecode[..len]) {
+                assert_eq!(err, "truncated bytecode");
+            }
+        }
+        assert_eq!(
+            Program::load(&bytecode[..bytecode.len() - 1]),
+            Err("truncated bytecode".to_string())
+        );
+
+        assert!(Program::load(b"D3BC")
+            .unwrap_err()
+            .starts_with("invalid program"));
+
+        // One name, "mul", and an opcode for a second one.
+        let mut unknown = MAGIC.to_vec();
+        write_str(&mut unknown, "standard");
+        write_varint(&mut unknown, 1);
+        write_str(&mut unknown, "mul");
+        unknown.extend_from_slice(&[2 << 1, 0, 8, 0]);
+        assert_eq!(Program::load(&unknown), Err("unknown opcode 4".to_string()));
+    }
+}
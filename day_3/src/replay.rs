@@ -0,0 +1,179 @@
+// Runs an exported program (see program.rs) and computes the sums of both parts, without
+// the memory it came from. The enabled state is worked out again from the do() and don't()
+// in the program, and has to agree with the state saved next to every instruction.
+
+use crate::instructions::{InstructionSet, Semantics};
+use crate::program::{Operand, Program};
+use crate::util::{DoDontState, Sums};
+
+// The value of an instruction that computes one, or None if it doesn't fit in a u128 (the
+// machine skips those too).
+fn evaluate(
+    instructions: &InstructionSet,
+    name: &str,
+    operands: &[Operand],
+) -> Result<Option<u128>, String> {
+    let Some(Semantics::Value(compute)) = instructions
+        .find(name, operands.len())
+        .map(|instruction| instruction.semantics)
+    else {
+        return Err(format!(
+            "{name} with {} operand(s) doesn't compute a value",
+            operands.len()
+        ));
+    };
+
+    let mut values: Vec<u128> = vec![];
+    for operand in operands {
+        let value = match operand {
+            Operand::Literal(value) => Some(*value as u128),
+            Operand::Nested { name, operands } => evaluate(instructions, name, operands)?,
+        };
+
+        match value {
+            Some(value) => values.push(value),
+            None => return Ok(None),
+        }
+    }
+
+    Ok(compute(&values))
+}
+
+pub fn run(program: &Program) -> Result<(u128, u128), String> {
+    let instructions = InstructionSet::by_name(&program.instructions).ok_or(format!(
+        "unknown instruction set '{}'",
+        program.instructions
+    ))?;
+
+    let mut state = DoDontState::new();
+    let mut sums = Sums::default();
+
+    for op in &program.ops {
+        let semantics = instructions
+            .find(&op.name, op.operands.len())
+            .ok_or(format!(
+                "{} with {} operand(s) at {} is not a {} instruction",
+                op.name,
+                op.operands.len(),
+                op.start,
+                program.instructions
+            ))?
+            .semantics;
+
+        state.apply(&semantics, op.start);
+        if state.is_enabled() != op.enabled {
+            return Err(format!(
+                "{} at {} is saved as {}",
+                op.name,
+                op.start,
+                if op.enabled { "enabled" } else { "disabled" }
+            ));
+        }
+
+        match semantics {
+            Semantics::Value(_) => {
+                // Skipped like the machine does when the sums would get too large.
+                if let Some(value) = evaluate(&instructions, &op.name, &op.operands)? {
+                    sums.add(value, state.is_enabled());
+                }
+            }
+            Semantics::Reset => sums.reset(),
+            Semantics::Enable | Semantics::Disable => {}
+        }
+    }
+
+    Ok((sums.all, sums.enabled))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{Grammar, Lexer, DEFAULT_MAX_DEPTH};
+    use crate::Machine;
+
+    // Extracts the program, saves it both ways and replays it. The sums have to be the ones
+    // the machine computes from the memory.
+    fn check(memory: &[u8], set_name: &str) -> (u128, u128) {
+        let instructions = InstructionSet::by_name(set_name).unwrap();
+        let lexer = Lexer::new(Grammar::Strict, &instructions).expressions(DEFAULT_MAX_DEPTH);
+        let tokens = lexer.tokenize(memory);
+
+        let mut machine = Machine::new(&instructions, false);
+        for spanned in &tokens {
+            machine.step(spanned);
+        }
+
+        let program = Program::extract(set_name, &instructions, &tokens);
+        let expected = (machine.sums.all, machine.sums.enabled);
+
+        assert_eq!(run(&program), Ok(expected));
+        for saved in [program.to_bytecode(), program.to_json().into_bytes()] {
+            assert_eq!(run(&Program::load(&saved).unwrap()), Ok(expected));
+        }
+
+        expected
+    }
+
+    #[test]
+    fn same_sums_as_the_machine() {
+        assert_eq!(
+            check(
+                b"xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))",
+                "standard"
+            ),
+            (161, 48)
+        );
+        assert_eq!(
+            check(
+                b"mul(mul(2,3),4)don't()add(1,mul(2,2,2))reset()mul(3,3)",
+                "extended"
+            ),
+            (9, 9)
+        );
+
+        let input = std::fs::read("input.txt").unwrap();
+        check(&input, "standard");
+    }
+
+    #[test]
+    fn saved_state_must_agree() {
+        let instructions = InstructionSet::standard();
+        let tokens = Lexer::new(Grammar::Strict, &instructions)
+            .tokenize(b"mul(1,2)don't()mul(3,4)do()mul(5,6)");
+        let mut program = Program::extract("standard", &instructions, &tokens);
+
+        assert_eq!(run(&program), Ok((44, 32)));
+
+        program.ops[2].enabled = true;
+        assert_eq!(
+            run(&program),
+            Err("mul at 15 is saved as enabled".to_string())
+        );
+
+        program.ops[2].enabled = false;
+        program.ops[1].enabled = true;
+        assert_eq!(
+            run(&program),
+            Err("don't at 8 is saved as enabled".to_string())
+        );
+    }
+
+    #[test]
+    fn unknown_instructions() {
+        let instructions = InstructionSet::extended();
+        let tokens = Lexer::new(Grammar::Strict, &instructions).tokenize(b"add(1,2)");
+        let mut program = Program::extract("extended", &instructions, &tokens);
+
+        program.instructions = "standard".to_string();
+        assert_eq!(
+            run(&program),
+            Err("add with 2 operand(s) at 0 is not a standard instruction".to_string())
+        );
+
+        program.instructions = "other".to_string();
+        assert_eq!(
+            run(&program),
+            Err("unknown instruction set 'other'".to_string())
+        );
+    }
+}
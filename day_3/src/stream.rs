@@ -0,0 +1,113 @@
+// Scans a memory dump of any size in fixed-size chunks. Only the current chunk and the start of
+// an instruction that goes on into the next chunk (like "mul(12," or "don'") are kept, so
+// memory use doesn't depend on the size of the dump. Instructions are never longer than
+// lexer::MAX_INSTRUCTION_LEN, so that's all that can be kept of one.
+
+use std::io::{self, BufRead};
+
+use crate::lexer::{Lexer, Spanned};
+
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+pub fn scan(
+    lexer: &Lexer,
+    mut reader: impl BufRead,
+    chunk_size: usize,
+    mut emit: impl FnMut(Spanned),
+) -> io::Result<()> {
+    // Bytes read but not scanned yet, starting at `offset` in the whole memory.
+    let mut pending: Vec<u8> = vec![];
+    let mut offset = 0;
+
+    loop {
+        let available = match reader.fill_buf() {
+            Ok(available) => available,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+
+        let len = available.len().min(chunk_size);
+        let at_end = len == 0;
+        pending.extend_from_slice(&available[..len]);
+        reader.consume(len);
+
+        let scanned = lexer.scan(&pending, offset, at_end, &mut emit);
+        pending.drain(..scanned);
+        offset += scanned;
+
+        if at_end {
+            return Ok(());
+        }
+    }
+}
+
+// Every instruction found, for checking streaming against scanning the memory whole.
+pub fn collect(lexer: &Lexer, reader: impl BufRead, chunk_size: usize) -> io::Result<Vec<Spanned>> {
+    let mut result: Vec<Spanned> = vec![];
+    scan(lexer, reader, chunk_size, |spanned| result.push(spanned))?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::collect;
+    use crate::instructions::InstructionSet;
+    use crate::lexer::{self, Grammar, Lexer};
+
+    const MEMORIES: &[&[u8]] = &[
+        b"xmul(2,4)%&mul[3,7]!@^do_not_mul(5,5)+mul(32,64]then(mul(11,8)mul(8,5))",
+        b"xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))",
+        b"mul( +12 , 3 )mul(1,2mul(0004,5)don'do()",
+        b"mul(mul(2,3),4)mul(add(1,2),mul(3,4,5))reset()mul(mul(1,",
+        b"\xffmul(1,1)\x00mul(999,999)mul(1000,1)",
+    ];
+
+    // Splitting the memory anywhere, or reading it one byte at a time, finds exactly the same
+    // instructions as scanning it whole.
+    #[test]
+    fn splits_find_the_same_instructions() {
+        let instructions = InstructionSet::extended();
+        let lexers = [
+            Lexer::new(Grammar::Strict, &instructions),
+            Lexer::new(Grammar::Lenient, &instructions),
+            Lexer::new(Grammar::Strict, &instructions).expressions(lexer::DEFAULT_MAX_DEPTH),
+            Lexer::new(Grammar::Lenient, &instructions).expressions(lexer::DEFAULT_MAX_DEPTH),
+        ];
+
+        for lexer in &lexers {
+            for memory in MEMORIES {
+                let expected = lexer.tokenize(memory);
+
+                for offset in 0..=memory.len() {
+                    let (first, second) = memory.split_at(offset);
+                    assert_eq!(
+                        collect(lexer, first.chain(second), usize::MAX).unwrap(),
+                        expected,
+                        "{:?} split at {offset}",
+                        String::from_utf8_lossy(memory)
+                    );
+                }
+
+                assert_eq!(collect(lexer, *memory, 1).unwrap(), expected);
+            }
+        }
+    }
+
+    // Nothing is kept of an instruction past lexer::MAX_INSTRUCTION_LEN, and what comes after
+    // it is still found.
+    #[test]
+    fn long_instructions_are_dropped() {
+        let instructions = InstructionSet::standard();
+        let lexer = Lexer::new(Grammar::Lenient, &instructions);
+        let mut memory = b"mul(".to_vec();
+        memory.resize(3 * lexer::MAX_INSTRUCTION_LEN, b' ');
+        memory.extend_from_slice(b"1,2)mul(2,3)");
+
+        let found = collect(&lexer, memory.as_slice(), 4096).unwrap();
+        assert_eq!(found, lexer.tokenize(&memory));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].token.to_string(), "mul(2,3)");
+    }
+}
@@ -1,76 +1,50 @@
-fn read_input() -> Vec<(u64, Vec<u64>)> {
-    use std::fs::File;
-    use std::io::{self, BufRead};
+mod number;
+mod operators;
+mod reverse;
 
-    let mut result: Vec<(u64, Vec<u64>)> = vec![];
+use num_bigint::BigUint;
 
-    let file = File::open("input.txt").unwrap();
-    let reader = io::BufReader::new(file);
+use number::Number;
+use operators::Operator;
+
+// Reads the equations as u64 or, with --big, as BigUint. A number that doesn't fit is an error,
+// it can't be skipped without changing the equation.
+fn read_input<N: Number>(reader: impl std::io::BufRead) -> Result<Vec<(N, Vec<N>)>, String> {
+    let mut result: Vec<(N, Vec<N>)> = vec![];
+
+    let parse = |index: usize, number_str: &str| {
+        number_str
+            .parse::<N>()
+            .map_err(|_| format!("line {}: '{number_str}' is not a {}", index + 1, N::NAME))
+    };
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| format!("line {}: {err}", index + 1))?;
 
-    for line in reader.lines().flatten() {
         // Split the line between the sum and the values.
-        let (sum_str, mut values_str) = line.split_once(':').unwrap();
-        let sum = sum_str.parse::<u64>().unwrap();
+        let (sum_str, mut values_str) = line
+            .split_once(':')
+            .ok_or(format!("line {}: missing ':'", index + 1))?;
+        let sum = parse(index, sum_str.trim())?;
         values_str = values_str.trim();
 
         let values = values_str
-            .split(' ')
-            .flat_map(|value_str| value_str.parse::<u64>())
-            .collect::<Vec<u64>>();
+            .split_whitespace()
+            .map(|value_str| parse(index, value_str))
+            .collect::<Result<Vec<N>, String>>()?;
+        if values.is_empty() {
+            return Err(format!("line {}: no values", index + 1));
+        }
+
         result.push((sum, values));
     }
 
-    return result;
+    Ok(result)
 }
 
 mod util {
-    use std::collections::HashMap;
-
-    fn generate_combinations_helper(
-        n: u64,
-        current: Vec<&'static str>,
-        result: &mut Vec<Vec<&'static str>>,
-        operators: &[&'static str],
-    ) {
-        if current.len() == n.try_into().unwrap() {
-            // Save result if we computed a combination with the max length.
-            result.push(current);
-            return;
-        }
-
-        for &op in operators {
-            // Next 2 lines are essentially a .clone() but we're ensuring that the copy will
-            // have the same preallocated capacity as the original.
-            let mut next: Vec<&str> = Vec::with_capacity(current.capacity());
-            // Clones and appends elements from current.
-            next.extend_from_slice(&current);
-            next.push(op);
-            generate_combinations_helper(n, next, result, operators);
-        }
-    }
-
-    fn generate_combinations(n: u64, operators: &[&'static str]) -> Vec<Vec<&'static str>> {
-        // We know the resulting vector will have <num_of_operators> to the power of <n> elements,
-        // so we use the Vec::with_capacity method to create the vector with enough preallocated memory
-        // from the start.
-        let mut result: Vec<Vec<&'static str>> =
-            Vec::with_capacity(operators.len().pow(n.try_into().unwrap()));
-        generate_combinations_helper(
-            n,
-            Vec::with_capacity(operators.len()),
-            &mut result,
-            operators,
-        );
-
-        return result;
-    }
-    pub(crate) struct CombinationRegerator {
-        cache: HashMap<u64, Vec<Vec<&'static str>>>,
-        operators: Vec<&'static str>,
-    }
-
-    // Class that creates all the possible combinations of length n over the given operations.
-    // For n = 4 and op = ["+", "*"] the class generates a vector containing:
+    // Every combination of length n over the given operators.
+    // For n = 4 and op = ["+", "*"] it goes through:
     // ["+", "+", "+", "+"],
     // ["+", "+", "+", "*"],
     // ["+", "+", "*", "+"],
@@ -78,79 +52,367 @@ mod util {
     // ...
     // ["*", "*", "*", "*"]
     //
-    // We're encapsulating this into a class in order to also cache the computed result for each n.
-    impl CombinationRegerator {
-        // Constructing the instance requires a list of operations supported being passed.
-        pub fn new(operators: Vec<&'static str>) -> Self {
+    // It works like an odometer where every position shows one of the operators. Each step
+    // turns the last position forward, and when it goes past the last operator it starts over
+    // and carries into the position before it. Only the current combination exists at any time.
+    //
+    // advance() returns the next combination in a buffer that is reused, so stepping through
+    // all of them doesn't allocate. Nothing is cached, stepping through the combinations again
+    // is cheaper than reading them back from a cache.
+    pub(crate) struct Combinations<'a, T> {
+        operators: &'a [T],
+        // Index of the operator at each position.
+        digits: Vec<usize>,
+        buffer: Vec<T>,
+        started: bool,
+        done: bool,
+    }
+
+    impl<'a, T: Copy> Combinations<'a, T> {
+        pub fn new(operators: &'a [T], n: usize) -> Self {
             Self {
-                cache: HashMap::new(),
                 operators,
+                digits: vec![0; n],
+                buffer: operators.first().map_or(vec![], |&first| vec![first; n]),
+                started: false,
+                // There is nothing to combine without operators.
+                done: operators.is_empty() && n > 0,
+            }
+        }
+
+        pub fn advance(&mut self) -> Option<&[T]> {
+            if self.done {
+                return None;
+            }
+
+            if !self.started {
+                self.started = true;
+                return Some(&self.buffer);
+            }
+
+            // Turn the last position forward, carrying into the ones before it.
+            for position in (0..self.digits.len()).rev() {
+                self.digits[position] += 1;
+
+                if self.digits[position] < self.operators.len() {
+                    self.buffer[position] = self.operators[self.digits[position]];
+                    return Some(&self.buffer);
+                }
+
+                self.digits[position] = 0;
+                self.buffer[position] = self.operators[0];
+            }
+
+            // Every position wrapped around, we're back at the first combination.
+            self.done = true;
+            None
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn all(operators: &[char], n: usize) -> Vec<String> {
+            let mut combinations = Combinations::new(operators, n);
+            let mut result: Vec<String> = vec![];
+
+            while let Some(combination) = combinations.advance() {
+                result.push(combination.iter().collect());
+            }
+
+            result
+        }
+
+        #[test]
+        fn odometer_order() {
+            assert_eq!(
+                all(&['+', '*'], 3),
+                ["+++", "++*", "+*+", "+**", "*++", "*+*", "**+", "***"]
+            );
+            assert_eq!(
+                all(&['a', 'b', 'c'], 2),
+                ["aa", "ab", "ac", "ba", "bb", "bc", "ca", "cb", "cc"]
+            );
+        }
+
+        #[test]
+        fn count() {
+            for operators in 1..=4 {
+                let symbols = &['a', 'b', 'c', 'd'][..operators];
+
+                for n in 0..=6 {
+                    assert_eq!(all(symbols, n).len(), operators.pow(n as u32));
+                }
             }
         }
 
-        // Returns the vector of combinations for a given n length.
-        // If the result was already computed before, we return the cached result,
-        // otherwise we do the new computation.
-        pub fn get(&mut self, n: u64) -> &Vec<Vec<&'static str>> {
-            return self
-                .cache
-                .entry(n)
-                .or_insert(generate_combinations(n, &self.operators));
+        #[test]
+        fn no_positions() {
+            assert_eq!(all(&['+', '*'], 0), [""]);
+            assert_eq!(all(&[], 0), [""]);
+        }
+
+        #[test]
+        fn no_operators() {
+            assert_eq!(all(&[], 3), Vec::<String>::new());
+        }
+
+        #[test]
+        fn done_stays_done() {
+            let mut combinations = Combinations::new(&['+'], 2);
+
+            assert_eq!(combinations.advance(), Some(&['+', '+'][..]));
+            assert_eq!(combinations.advance(), None);
+            assert_eq!(combinations.advance(), None);
         }
     }
 }
 
 // Function for the fold algorithm taking the left value and a tuple between the right value
-// and the string representing the operation to be applied between them. 
-fn evaluator(lhs: u64, (rhs, operation): (&u64, &&str)) -> u64 {
-    // In Rust we can match any value type, including strings.
-    match *operation {
-        "+" => lhs + rhs,
-        "*" => lhs * rhs,
-        // For concatenation we create the string representation of the two values concatenated
-        // and then we parse the result back into u64.
-        "||" => format!("{}{}", lhs, rhs).parse::<u64>().unwrap(),
-        _ => panic!("Shouldn't happen"),
+// and the operation to be applied between them. Each operator knows how to apply itself, see
+// operators.rs. Returns None if the result doesn't fit, which stops the fold.
+fn evaluator<N: Number>(lhs: N, (rhs, operation): (&N, &&dyn Operator<N>)) -> Option<N> {
+    operation.checked_apply(&lhs, rhs)
+}
+
+// Places the operators between the values and computes the result from left to right. None
+// if the result, or any step on the way, doesn't fit: it can't be equal to the sum then.
+fn evaluate<N: Number>(values: &[N], combo: &[&dyn Operator<N>]) -> Option<N> {
+    // Split off the initial value so we have our init value for the fold algorithm.
+    let (first, rest) = values.split_first().unwrap();
+    // Zip the rest of the values alongside an operation.
+    let mut zipped_with_operation = rest.iter().zip(combo.iter());
+    // try_fold is a fold that stops at the first None, it requires an initial value and a predicate.
+    zipped_with_operation.try_fold(first.clone(), evaluator)
+}
+
+// Adds up the sums like Iterator::sum, but returns an error instead of overflowing.
+fn total<'a, N: Number>(mut sums: impl Iterator<Item = &'a N>) -> Result<N, String> {
+    sums.try_fold(N::zero(), |total, sum| total.checked_add(sum))
+        .ok_or(format!("the total doesn't fit in a {}", N::NAME))
+}
+
+// Tries every combination of operators, folding the values left to right for each one, and
+// returns the first combination that gives the sum.
+fn brute_force<N: Number>(
+    sum: &N,
+    values: &[N],
+    operators: &[&'static dyn Operator<N>],
+) -> Option<Vec<&'static dyn Operator<N>>> {
+    // One operation must be placed between each value, so the length of the operation combinations must
+    // be the length of the values - 1.
+    let mut combinations = util::Combinations::new(operators, values.len() - 1);
+
+    while let Some(combo) = combinations.advance() {
+        if evaluate(values, combo).as_ref() == Some(sum) {
+            return Some(combo.to_vec());
+        }
+    }
+
+    None
+}
+
+// Times both solvers on the whole input. They have to agree on which equations can be solved,
+// and every solution of the reverse search has to give the sum when computed forwards.
+fn bench<N: Number>(
+    input: &[(N, Vec<N>)],
+    operators: &[&'static dyn Operator<N>],
+) -> Result<(), String> {
+    let start = std::time::Instant::now();
+    let brute_force_total = total(
+        input
+            .iter()
+            .filter(|(sum, values)| brute_force(sum, values, operators).is_some())
+            .map(|(sum, _)| sum),
+    )?;
+    let brute_force_time = start.elapsed();
+
+    let start = std::time::Instant::now();
+    let reverse_total = total(
+        input
+            .iter()
+            .filter(|(sum, values)| reverse::solve(sum, values, operators).is_some())
+            .map(|(sum, _)| sum),
+    )?;
+    let reverse_time = start.elapsed();
+
+    for (sum, values) in input {
+        if let Some(combo) = reverse::solve(sum, values, operators) {
+            if evaluate(values, &combo).as_ref() != Some(sum) {
+                println!("FAIL: {sum}: {values:?} is solved with {combo:?}");
+                std::process::exit(1);
+            }
+        }
     }
+
+    println!("Brute force:    {brute_force_total} in {brute_force_time:?}");
+    println!("Reverse search: {reverse_total} in {reverse_time:?}");
+    println!(
+        "Reverse search is {:.0}x faster",
+        brute_force_time.as_secs_f64() / reverse_time.as_secs_f64()
+    );
+
+    if brute_force_total != reverse_total {
+        println!("FAIL: the solvers disagree");
+        std::process::exit(1);
+    }
+
+    Ok(())
 }
 
+// Flags:
+//
+//   --brute-force   try every combination of operators instead of searching backwards
+//   --bench         time both ways of solving the equations and compare them
+//   --operators L   the operators to place between the values, as a comma separated list of
+//                   + * || - / % ^ max min (quoted for the shell), "+,*,||" by default
+//   --big           read the numbers as big integers, for sums and values that don't fit in a
+//                   u64 (slower)
 fn main() {
-    let input = read_input();
-    let mut total_calibration_result = 0u64;
-    // This is a valid solution for both day 1 and 2, simply add/remove the "||" operator.
-    let mut generator = util::CombinationRegerator::new(vec!["+", "*", "||"]);
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+
+    let result = if args.iter().any(|arg| arg == "--big") {
+        run::<BigUint>(&args)
+    } else {
+        run::<u64>(&args)
+    };
+
+    if let Err(err) = result {
+        eprintln!("error: {err}");
+        std::process::exit(2);
+    }
+}
+
+fn run<N: Number>(args: &[String]) -> Result<(), String> {
+    let file = std::fs::File::open("input.txt").map_err(|err| format!("input.txt: {err}"))?;
+    let input = read_input::<N>(std::io::BufReader::new(file))?;
+    let mut total_calibration_result = N::zero();
+    // This is a valid solution for both day 1 and 2, simply pass --operators "+,*" for day 1.
+    let list = match args.iter().position(|arg| arg == "--operators") {
+        Some(index) => args
+            .get(index + 1)
+            .map(String::as_str)
+            .ok_or("missing value for --operators".to_string())?,
+        None => "+,*,||",
+    };
+    let operators = operators::parse_list::<N>(list)?;
+
+    if args.iter().any(|arg| arg == "--bench") {
+        return bench(&input, &operators);
+    }
+
+    let use_brute_force = args.iter().any(|arg| arg == "--brute-force");
 
     for (sum, values) in input {
         println!("{}: {:?}", sum, values);
 
-        // One operation must be placed between each value, so the length of the vector of operation combinations must
-        // be the length of the values - 1.
-        for combo in generator.get((values.len() - 1).try_into().unwrap()) {
-            // Split off the initial value so we have our init value for the fold algorithm. 
-            let (first, rest) = values.split_first().unwrap();
-            // Zip the rest of the values alongside an operation.
-            let zipped_with_operation = rest.iter().zip(combo.iter());
-            // Fold is Rust's equivalent of std::accumulate or std::fold_left, it requires an initial value and a predicate.
-            let result = zipped_with_operation.fold(*first, evaluator);
-
-            // If our evaluated expression results in the specified sum, add said sum to total_calibration_result.
-            if result == sum {
-                print!("Solution found: ");
-                print!("{}", values.first().unwrap());
-
-                for (op, value) in combo.iter().zip(&values[1..]) {
-                    print!("{op}{value}");
-                }
-
-                println!();
+        let solution = if use_brute_force {
+            brute_force(&sum, &values, &operators)
+        } else {
+            reverse::solve(&sum, &values, &operators)
+        };
 
-                total_calibration_result += sum;
+        // If our evaluated expression results in the specified sum, add said sum to total_calibration_result.
+        if let Some(combo) = solution {
+            print!("Solution found: ");
+            print!("{}", values.first().unwrap());
 
-                break;
+            for (op, value) in combo.iter().zip(&values[1..]) {
+                print!("{}{value}", op.symbol());
             }
+
+            println!();
+
+            total_calibration_result = total([&total_calibration_result, &sum].into_iter())?;
         }
     }
 
     // 169122112716571
     println!("Total calibration result = {total_calibration_result}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_errors() {
+        let read = |input: &[u8]| read_input::<u64>(input);
+
+        assert_eq!(
+            read(b"190: 10 19\n3267: 81 40 27\n"),
+            Ok(vec![(190, vec![10, 19]), (3267, vec![81, 40, 27])])
+        );
+        assert_eq!(
+            read(b"190: 10 19\n5:\n"),
+            Err("line 2: no values".to_string())
+        );
+        assert_eq!(read(b"5:   \n"), Err("line 1: no values".to_string()));
+        assert_eq!(read(b"190 10 19\n"), Err("line 1: missing ':'".to_string()));
+        assert_eq!(
+            read(b"190: 10 x\n"),
+            Err("line 1: 'x' is not a u64".to_string())
+        );
+        assert_eq!(
+            read(b"18446744073709551616: 1\n"),
+            Err("line 1: '18446744073709551616' is not a u64".to_string())
+        );
+        assert_eq!(
+            read(b"1: 1\n2: \xff\n"),
+            Err("line 2: stream did not contain valid UTF-8".to_string())
+        );
+    }
+
+    // Both solvers, which have to agree.
+    fn solvable<N: Number>(sum: &N, values: &[N], list: &str) -> bool {
+        let operators = operators::parse_list::<N>(list).unwrap();
+        let solution = reverse::solve(sum, values, &operators);
+
+        assert_eq!(
+            solution.is_some(),
+            brute_force(sum, values, &operators).is_some()
+        );
+        solution.is_some()
+    }
+
+    #[test]
+    fn overflow_is_pruned() {
+        assert!(solvable(&u64::MAX, &[u64::MAX, 1], "*"));
+        assert!(!solvable(&u64::MAX, &[u64::MAX, 2], "+"));
+        assert!(!solvable(&u64::MAX, &[u64::MAX, 2, 2], "+,*"));
+        // A step that doesn't fit is dropped, the others still count.
+        assert!(solvable(&u64::MAX, &[u64::MAX, 2, 2], "+,-"));
+        assert!(solvable(&(u64::MAX - 1), &[u64::MAX / 2, 2], "*"));
+        assert!(!solvable(&u64::MAX, &[1, u64::MAX], "||"));
+        assert!(solvable(&u64::MAX, &[0, u64::MAX], "||"));
+    }
+
+    #[test]
+    fn big_numbers() {
+        let input =
+            read_input::<BigUint>(&b"36893488147419103230: 18446744073709551615 2\n"[..]).unwrap();
+        let (sum, values) = &input[0];
+
+        assert!(*sum > BigUint::from(u64::MAX));
+        assert!(solvable(sum, values, "+,*"));
+        assert!(solvable(
+            sum,
+            &[values[0].clone(), BigUint::from(1u32), values[0].clone()],
+            "+,*"
+        ));
+        assert!(!solvable(sum, values, "+,||"));
+        assert!(read_input::<u64>(&b"36893488147419103230: 18446744073709551615 2\n"[..]).is_err());
+    }
+
+    #[test]
+    fn total_overflow() {
+        assert_eq!(total([u64::MAX - 1, 1].iter()), Ok(u64::MAX));
+        assert_eq!(
+            total([u64::MAX, 1].iter()),
+            Err("the total doesn't fit in a u64".to_string())
+        );
+    }
 }
@@ -0,0 +1,145 @@
+// The numbers the equations are made of. By default they are u64, every operation is checked
+// and a combination with a step that doesn't fit is dropped: with the puzzle's operators the
+// result only grows, so it could never be equal to a target that fits in a u64. With --big
+// they are BigUint instead, for targets and values that don't fit, or to let -, / and % bring
+// a big step back down.
+//
+// Most of the arithmetic comes from the num crates, which implement it for both. Only what
+// differs between them is here.
+
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+use std::str::FromStr;
+
+use num_bigint::BigUint;
+use num_integer::{Integer, Roots};
+use num_traits::{CheckedAdd, CheckedDiv, CheckedMul, CheckedSub};
+
+// BigUint powers are only computed up to this many bits, about 300 000 digits, so something
+// like 999 ^ 999 ^ 999 doesn't take all the memory.
+const MAX_BITS: u64 = 1 << 20;
+
+pub trait Number:
+    Clone
+    + Ord
+    + Hash
+    + Debug
+    + Display
+    + FromStr
+    + Integer
+    + Roots
+    + CheckedAdd
+    + CheckedSub
+    + CheckedMul
+    + CheckedDiv
+    + 'static
+{
+    // The name of the type, for the errors.
+    const NAME: &'static str;
+
+    // self ^ exponent, None if it doesn't fit.
+    fn checked_power(&self, exponent: u32) -> Option<Self>;
+
+    // The value as an exponent, None if it doesn't fit in a u32.
+    fn exponent(&self) -> Option<u32>;
+
+    // 10 to the power of the number of digits, where 0 has 1 digit. None if it doesn't fit.
+    fn shift(&self) -> Option<Self>;
+}
+
+impl Number for u64 {
+    const NAME: &'static str = "u64";
+
+    fn checked_power(&self, exponent: u32) -> Option<Self> {
+        self.checked_pow(exponent)
+    }
+
+    fn exponent(&self) -> Option<u32> {
+        u32::try_from(*self).ok()
+    }
+
+    fn shift(&self) -> Option<Self> {
+        10u64.checked_pow(self.checked_ilog10().unwrap_or(0) + 1)
+    }
+}
+
+impl Number for BigUint {
+    const NAME: &'static str = "BigUint";
+
+    fn checked_power(&self, exponent: u32) -> Option<Self> {
+        // The result has at least (bits - 1) * exponent bits, 0 for 0 and 1.
+        if (self.bits().saturating_sub(1)).saturating_mul(exponent as u64) > MAX_BITS {
+            return None;
+        }
+
+        Some(self.pow(exponent))
+    }
+
+    fn exponent(&self) -> Option<u32> {
+        u32::try_from(self).ok()
+    }
+
+    fn shift(&self) -> Option<Self> {
+        Some(BigUint::from(10u32).pow(self.to_string().len() as u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shift() {
+        assert_eq!(0u64.shift(), Some(10));
+        assert_eq!(9u64.shift(), Some(10));
+        assert_eq!(10u64.shift(), Some(100));
+        assert_eq!(999_999_999_999_999_999u64.shift(), Some(10u64.pow(18)));
+        // 10^19 fits, 10^20 doesn't.
+        assert_eq!(1_000_000_000_000_000_000u64.shift(), Some(10u64.pow(19)));
+        assert_eq!(10_000_000_000_000_000_000u64.shift(), None);
+        assert_eq!(u64::MAX.shift(), None);
+
+        assert_eq!(BigUint::from(0u32).shift(), Some(BigUint::from(10u32)));
+        assert_eq!(
+            BigUint::from(u64::MAX).shift(),
+            Some(BigUint::from(10u32).pow(20))
+        );
+    }
+
+    #[test]
+    fn exponent() {
+        assert_eq!((u32::MAX as u64).exponent(), Some(u32::MAX));
+        assert_eq!((u32::MAX as u64 + 1).exponent(), None);
+        assert_eq!(BigUint::from(7u32).exponent(), Some(7));
+        assert_eq!(BigUint::from(u64::MAX).exponent(), None);
+    }
+
+    #[test]
+    fn power() {
+        assert_eq!(3u64.checked_power(40), Some(3u64.pow(40)));
+        assert_eq!(3u64.checked_power(41), None);
+        assert_eq!(2u64.checked_power(64), None);
+    }
+
+    #[test]
+    fn big_power_is_capped() {
+        let two = BigUint::from(2u32);
+        let cap = MAX_BITS as u32;
+
+        assert_eq!(
+            two.checked_power(cap).map(|power| power.bits()),
+            Some(MAX_BITS + 1)
+        );
+        assert_eq!(two.checked_power(cap + 1), None);
+        assert_eq!(BigUint::from(999u32).checked_power(999 * 999), None);
+        // 0 and 1 don't grow.
+        assert_eq!(
+            BigUint::from(1u32).checked_power(u32::MAX),
+            Some(BigUint::from(1u32))
+        );
+        assert_eq!(
+            BigUint::from(0u32).checked_power(u32::MAX),
+            Some(BigUint::from(0u32))
+        );
+    }
+}
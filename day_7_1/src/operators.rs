@@ -0,0 +1,403 @@
+// The operators that can be placed between the values of an equation. The puzzle only has +, *
+// and || (concatenation), the others can be picked from the command line with --operators.
+//
+// Each operator knows how to compute "lhs op rhs" and, for the reverse search (see
+// reverse.rs), how to undo it: which lhs gives a result for a given rhs. Both work for any
+// Number (see number.rs) and never overflow: a result that doesn't fit is None.
+
+use std::fmt;
+
+use crate::number::Number;
+
+// What the reverse search learns when undoing "lhs op rhs = result".
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Inverse<N> {
+    // No lhs gives the result.
+    Impossible,
+    // This lhs is the only one that gives the result.
+    Exactly(N),
+    // Many values of lhs could give the result (like any lhs % 10 = 3), they have to be
+    // computed forwards.
+    Unknown,
+}
+
+pub trait Operator<N> {
+    // How the operator is written, on the command line and in the solutions.
+    fn symbol(&self) -> &'static str;
+
+    // lhs op rhs, or None if the result doesn't fit in N (or doesn't exist, like 1 - 2).
+    fn checked_apply(&self, lhs: &N, rhs: &N) -> Option<N>;
+
+    fn inverse(&self, result: &N, rhs: &N) -> Inverse<N>;
+}
+
+impl<N> fmt::Debug for dyn Operator<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+fn exactly<N>(lhs: Option<N>) -> Inverse<N> {
+    lhs.map_or(Inverse::Impossible, Inverse::Exactly)
+}
+
+struct Add;
+
+impl<N: Number> Operator<N> for Add {
+    fn symbol(&self) -> &'static str {
+        "+"
+    }
+
+    fn checked_apply(&self, lhs: &N, rhs: &N) -> Option<N> {
+        lhs.checked_add(rhs)
+    }
+
+    fn inverse(&self, result: &N, rhs: &N) -> Inverse<N> {
+        exactly(result.checked_sub(rhs))
+    }
+}
+
+struct Multiply;
+
+impl<N: Number> Operator<N> for Multiply {
+    fn symbol(&self) -> &'static str {
+        "*"
+    }
+
+    fn checked_apply(&self, lhs: &N, rhs: &N) -> Option<N> {
+        lhs.checked_mul(rhs)
+    }
+
+    fn inverse(&self, result: &N, rhs: &N) -> Inverse<N> {
+        match (result.is_zero(), rhs.is_zero()) {
+            // Anything times 0 is 0.
+            (true, true) => Inverse::Unknown,
+            (false, true) => Inverse::Impossible,
+            _ => exactly(result.is_multiple_of(rhs).then(|| result.div_floor(rhs))),
+        }
+    }
+}
+
+struct Concatenate;
+
+impl<N: Number> Operator<N> for Concatenate {
+    fn symbol(&self) -> &'static str {
+        "||"
+    }
+
+    fn checked_apply(&self, lhs: &N, rhs: &N) -> Option<N> {
+        // "lhs || rhs" is lhs * 10^digits + rhs.
+        match rhs.shift() {
+            Some(shift) => lhs.checked_mul(&shift)?.checked_add(rhs),
+            None => lhs.is_zero().then(|| rhs.clone()),
+        }
+    }
+
+    fn inverse(&self, result: &N, rhs: &N) -> Inverse<N> {
+        // The result has to end with the digits of rhs, the lhs is what comes before them.
+        let Some(rest) = result.checked_sub(rhs) else {
+            return Inverse::Impossible;
+        };
+
+        match rhs.shift() {
+            Some(shift) => exactly(rest.is_multiple_of(&shift).then(|| rest.div_floor(&shift))),
+            // Only a lhs of 0 leaves rhs on its own.
+            None => exactly(rest.is_zero().then(N::zero)),
+        }
+    }
+}
+
+struct Subtract;
+
+impl<N: Number> Operator<N> for Subtract {
+    fn symbol(&self) -> &'static str {
+        "-"
+    }
+
+    fn checked_apply(&self, lhs: &N, rhs: &N) -> Option<N> {
+        lhs.checked_sub(rhs)
+    }
+
+    fn inverse(&self, result: &N, rhs: &N) -> Inverse<N> {
+        exactly(result.checked_add(rhs))
+    }
+}
+
+// Integer division, rounded down.
+struct Divide;
+
+impl<N: Number> Operator<N> for Divide {
+    fn symbol(&self) -> &'static str {
+        "/"
+    }
+
+    fn checked_apply(&self, lhs: &N, rhs: &N) -> Option<N> {
+        lhs.checked_div(rhs)
+    }
+
+    fn inverse(&self, result: &N, rhs: &N) -> Inverse<N> {
+        if rhs.is_zero() {
+            Inverse::Impossible
+        } else if rhs.is_one() {
+            Inverse::Exactly(result.clone())
+        } else {
+            // Every lhs from result * rhs to result * rhs + rhs - 1 works.
+            Inverse::Unknown
+        }
+    }
+}
+
+struct Remainder;
+
+impl<N: Number> Operator<N> for Remainder {
+    fn symbol(&self) -> &'static str {
+        "%"
+    }
+
+    fn checked_apply(&self, lhs: &N, rhs: &N) -> Option<N> {
+        (!rhs.is_zero()).then(|| lhs.mod_floor(rhs))
+    }
+
+    fn inverse(&self, result: &N, rhs: &N) -> Inverse<N> {
+        if result >= rhs {
+            Inverse::Impossible
+        } else {
+            Inverse::Unknown
+        }
+    }
+}
+
+struct Power;
+
+impl<N: Number> Operator<N> for Power {
+    fn symbol(&self) -> &'static str {
+        "^"
+    }
+
+    fn checked_apply(&self, lhs: &N, rhs: &N) -> Option<N> {
+        match rhs.exponent() {
+            Some(exponent) => lhs.checked_power(exponent),
+            // Only 0 and 1 stay that small with such a big exponent.
+            None => (lhs.is_zero() || lhs.is_one()).then(|| lhs.clone()),
+        }
+    }
+
+    fn inverse(&self, result: &N, rhs: &N) -> Inverse<N> {
+        match rhs.exponent() {
+            // Anything to the power of 0 is 1.
+            Some(0) if result.is_one() => Inverse::Unknown,
+            Some(0) => Inverse::Impossible,
+            Some(n) => {
+                let root = result.nth_root(n);
+                exactly((root.checked_power(n).as_ref() == Some(result)).then_some(root))
+            }
+            // They are their own power, see checked_apply.
+            None if result.is_zero() || result.is_one() => Inverse::Exactly(result.clone()),
+            None => Inverse::Impossible,
+        }
+    }
+}
+
+struct Max;
+
+impl<N: Number> Operator<N> for Max {
+    fn symbol(&self) -> &'static str {
+        "max"
+    }
+
+    fn checked_apply(&self, lhs: &N, rhs: &N) -> Option<N> {
+        Some(lhs.max(rhs).clone())
+    }
+
+    fn inverse(&self, result: &N, rhs: &N) -> Inverse<N> {
+        match result.cmp(rhs) {
+            std::cmp::Ordering::Greater => Inverse::Exactly(result.clone()),
+            // Any lhs up to rhs.
+            std::cmp::Ordering::Equal => Inverse::Unknown,
+            std::cmp::Ordering::Less => Inverse::Impossible,
+        }
+    }
+}
+
+struct Min;
+
+impl<N: Number> Operator<N> for Min {
+    fn symbol(&self) -> &'static str {
+        "min"
+    }
+
+    fn checked_apply(&self, lhs: &N, rhs: &N) -> Option<N> {
+        Some(lhs.min(rhs).clone())
+    }
+
+    fn inverse(&self, result: &N, rhs: &N) -> Inverse<N> {
+        match result.cmp(rhs) {
+            std::cmp::Ordering::Less => Inverse::Exactly(result.clone()),
+            // Any lhs from rhs up.
+            std::cmp::Ordering::Equal => Inverse::Unknown,
+            std::cmp::Ordering::Greater => Inverse::Impossible,
+        }
+    }
+}
+
+// Every operator that can be picked, the first three are the puzzle's.
+pub fn all<N: Number>() -> [&'static dyn Operator<N>; 9] {
+    [
+        &Add,
+        &Multiply,
+        &Concatenate,
+        &Subtract,
+        &Divide,
+        &Remainder,
+        &Power,
+        &Max,
+        &Min,
+    ]
+}
+
+pub fn by_symbol<N: Number>(symbol: &str) -> Option<&'static dyn Operator<N>> {
+    all()
+        .into_iter()
+        .find(|operator| operator.symbol() == symbol)
+}
+
+// Reads a comma separated list of symbols, like "+,*,||".
+pub fn parse_list<N: Number>(list: &str) -> Result<Vec<&'static dyn Operator<N>>, String> {
+    list.split(',')
+        .map(|symbol| {
+            by_symbol(symbol.trim()).ok_or_else(|| {
+                let known = all::<N>()
+                    .iter()
+                    .map(|operator| operator.symbol())
+                    .collect::<Vec<&str>>();
+                format!(
+                    "unknown operator '{symbol}', expected one of {}",
+                    known.join(" ")
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use num_bigint::BigUint;
+
+    // Small values, plus an exponent too big for a u32 and values at the edge of a u64.
+    const VALUES: [u64; 8] = [5_000_000_000, u64::MAX - 1, u64::MAX, 0, 1, 2, 3, 10];
+
+    fn values<N: Number + From<u64>>() -> Vec<N> {
+        VALUES
+            .into_iter()
+            .chain(4..=40)
+            .chain([99, 100, 101])
+            .map(N::from)
+            .collect()
+    }
+
+    // Undoing an operator gives back the lhs it was applied to, unless many could give the
+    // same result.
+    fn inverse_undoes_apply<N: Number + From<u64>>() {
+        let values = values::<N>();
+
+        for operator in all::<N>() {
+            for lhs in &values {
+                for rhs in &values {
+                    let Some(result) = operator.checked_apply(lhs, rhs) else {
+                        continue;
+                    };
+
+                    let inverse = operator.inverse(&result, rhs);
+                    assert!(
+                        inverse == Inverse::Exactly(lhs.clone()) || inverse == Inverse::Unknown,
+                        "{lhs} {} {rhs} = {result}, but the inverse is {inverse:?}",
+                        operator.symbol()
+                    );
+                }
+            }
+        }
+    }
+
+    // An lhs given by the inverse gives the result back.
+    fn apply_redoes_inverse<N: Number + From<u64>>() {
+        let values = values::<N>();
+
+        for operator in all::<N>() {
+            for result in &values {
+                for rhs in &values {
+                    if let Inverse::Exactly(lhs) = operator.inverse(result, rhs) {
+                        assert_eq!(
+                            operator.checked_apply(&lhs, rhs).as_ref(),
+                            Some(result),
+                            "{lhs} {} {rhs}",
+                            operator.symbol()
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn inverses_u64() {
+        inverse_undoes_apply::<u64>();
+        apply_redoes_inverse::<u64>();
+    }
+
+    #[test]
+    fn inverses_big() {
+        inverse_undoes_apply::<BigUint>();
+        apply_redoes_inverse::<BigUint>();
+    }
+
+    #[test]
+    fn edge_cases() {
+        let power = by_symbol::<u64>("^").unwrap();
+        assert_eq!(power.checked_apply(&1, &5_000_000_000), Some(1));
+        assert_eq!(power.checked_apply(&0, &5_000_000_000), Some(0));
+        assert_eq!(power.checked_apply(&2, &5_000_000_000), None);
+        assert_eq!(power.checked_apply(&0, &0), Some(1));
+        assert_eq!(power.inverse(&1, &0), Inverse::Unknown);
+        assert_eq!(power.inverse(&2, &0), Inverse::Impossible);
+
+        let remainder = by_symbol::<u64>("%").unwrap();
+        assert_eq!(remainder.checked_apply(&7, &0), None);
+        assert_eq!(remainder.inverse(&3, &0), Inverse::Impossible);
+
+        let concatenate = by_symbol::<u64>("||").unwrap();
+        assert_eq!(concatenate.checked_apply(&12, &345), Some(12345));
+        assert_eq!(concatenate.checked_apply(&0, &u64::MAX), Some(u64::MAX));
+        assert_eq!(concatenate.checked_apply(&1, &u64::MAX), None);
+        assert_eq!(
+            concatenate.inverse(&u64::MAX, &u64::MAX),
+            Inverse::Exactly(0)
+        );
+
+        for symbol in ["max", "min"] {
+            let operator = by_symbol::<u64>(symbol).unwrap();
+            assert_eq!(operator.inverse(&5, &5), Inverse::Unknown);
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let symbols = |list: &str| {
+            parse_list::<u64>(list).map(|operators| {
+                operators
+                    .iter()
+                    .map(|operator| operator.symbol())
+                    .collect::<Vec<&str>>()
+            })
+        };
+
+        assert_eq!(symbols("+,*,||"), Ok(vec!["+", "*", "||"]));
+        assert_eq!(symbols(" max , % "), Ok(vec!["max", "%"]));
+        assert_eq!(
+            symbols("+,**"),
+            Err("unknown operator '**', expected one of + * || - / % ^ max min".to_string())
+        );
+        assert!(symbols("").is_err());
+    }
+}
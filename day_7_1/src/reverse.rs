@@ -0,0 +1,212 @@
+// Solves an equation backwards, starting from the target instead of the first value.
+//
+// The last operator is always applied last, so for "target = ... op last" we only need to
+// check which operators could have produced the target from the last value, and undo them:
+//
+//   +    the rest must give target - last, impossible if last is bigger than the target
+//   *    the rest must give target / last, impossible if the target isn't divisible by last
+//   ||   the rest must give the target without the digits of last at the end, impossible if
+//        the target doesn't end with them
+//
+// Every impossible branch is dropped right away, together with all the combinations of the
+// operators before it. That's why this is so much faster than trying every combination.
+//
+// Some operators can't always be undone, like % or anything times 0, where many values give
+// the same result. For those branches the values before are computed forwards instead: every
+// result the first n values can give, with the operator that gave each one. They don't depend
+// on the target, so they are computed once per equation and reused by every branch.
+
+use std::collections::HashMap;
+
+use crate::number::Number;
+use crate::operators::{Inverse, Operator};
+
+// The operator placed before a value and the result of the values before it, None for the
+// first value.
+type Step<N> = Option<(&'static dyn Operator<N>, N)>;
+
+struct Forwards<'a, N: 'static> {
+    values: &'a [N],
+    operators: &'a [&'static dyn Operator<N>],
+    // levels[n] has every result of the first n + 1 values.
+    levels: Vec<HashMap<N, Step<N>>>,
+}
+
+impl<'a, N: Number> Forwards<'a, N> {
+    fn new(values: &'a [N], operators: &'a [&'static dyn Operator<N>]) -> Self {
+        Self {
+            values,
+            operators,
+            levels: vec![HashMap::from([(values[0].clone(), None)])],
+        }
+    }
+
+    // Every result of the first n + 1 values.
+    fn level(&mut self, n: usize) -> &HashMap<N, Step<N>> {
+        while self.levels.len() <= n {
+            let rhs = &self.values[self.levels.len()];
+            let mut next: HashMap<N, Step<N>> = HashMap::new();
+
+            for lhs in self.levels.last().unwrap().keys() {
+                for &operator in self.operators {
+                    if let Some(result) = operator.checked_apply(lhs, rhs) {
+                        next.entry(result)
+                            .or_insert_with(|| Some((operator, lhs.clone())));
+                    }
+                }
+            }
+
+            self.levels.push(next);
+        }
+
+        &self.levels[n]
+    }
+
+    // The operators that give `result` from the first n + 1 values, from left to right.
+    fn operators_for(&self, n: usize, result: &N, chosen: &mut Vec<&'static dyn Operator<N>>) {
+        let start = chosen.len();
+        let mut result = result;
+
+        for level in self.levels[..=n].iter().rev() {
+            if let Some((operator, lhs)) = &level[result] {
+                chosen.push(*operator);
+                result = lhs;
+            }
+        }
+
+        chosen[start..].reverse();
+    }
+}
+
+fn search<N: Number>(
+    target: &N,
+    values: &[N],
+    operators: &[&'static dyn Operator<N>],
+    forwards: &mut Forwards<N>,
+    chosen: &mut Vec<&'static dyn Operator<N>>,
+) -> bool {
+    let (last, rest) = values.split_last().unwrap();
+
+    if rest.is_empty() {
+        return target == last;
+    }
+
+    for &operator in operators {
+        let found = match operator.inverse(target, last) {
+            Inverse::Impossible => false,
+            Inverse::Exactly(lhs) => search(&lhs, rest, operators, forwards, chosen),
+            Inverse::Unknown => {
+                let n = rest.len() - 1;
+                let lhs = forwards
+                    .level(n)
+                    .keys()
+                    .find(|lhs| operator.checked_apply(lhs, last).as_ref() == Some(target))
+                    .cloned();
+
+                if let Some(lhs) = &lhs {
+                    forwards.operators_for(n, lhs, chosen);
+                }
+
+                lhs.is_some()
+            }
+        };
+
+        if found {
+            chosen.push(operator);
+            return true;
+        }
+    }
+
+    false
+}
+
+// Returns the operators to place between the values, from left to right, if there's a way
+// to get the target.
+pub fn solve<N: Number>(
+    target: &N,
+    values: &[N],
+    operators: &[&'static dyn Operator<N>],
+) -> Option<Vec<&'static dyn Operator<N>>> {
+    let mut chosen: Vec<&'static dyn Operator<N>> = Vec::with_capacity(values.len());
+    let mut forwards = Forwards::new(values, operators);
+
+    if search(target, values, operators, &mut forwards, &mut chosen) {
+        Some(chosen)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operators;
+
+    const EXAMPLE: [(u64, &[u64]); 9] = [
+        (190, &[10, 19]),
+        (3267, &[81, 40, 27]),
+        (83, &[17, 5]),
+        (156, &[15, 6]),
+        (7290, &[6, 8, 6, 15]),
+        (161011, &[16, 10, 13]),
+        (192, &[17, 8, 14]),
+        (21037, &[9, 7, 18, 13]),
+        (292, &[11, 6, 16, 20]),
+    ];
+
+    // The targets of the equations that can be solved, checked against the brute force.
+    fn solved(equations: &[(u64, &[u64])], list: &str) -> Vec<u64> {
+        let operators = operators::parse_list::<u64>(list).unwrap();
+        let mut result: Vec<u64> = vec![];
+
+        for &(target, values) in equations {
+            let solution = solve(&target, values, &operators);
+
+            assert_eq!(
+                solution.is_some(),
+                crate::brute_force(&target, values, &operators).is_some(),
+                "{target}: {values:?}"
+            );
+
+            if let Some(combo) = solution {
+                assert_eq!(combo.len(), values.len() - 1);
+                assert_eq!(crate::evaluate(values, &combo), Some(target));
+                result.push(target);
+            }
+        }
+
+        result
+    }
+
+    #[test]
+    fn example_part_1() {
+        assert_eq!(solved(&EXAMPLE, "+,*"), [190, 3267, 292]);
+    }
+
+    #[test]
+    fn example_part_2() {
+        assert_eq!(solved(&EXAMPLE, "+,*,||"), [190, 3267, 156, 7290, 192, 292]);
+    }
+
+    #[test]
+    fn solutions_from_left_to_right() {
+        let operators = operators::parse_list::<u64>("+,*,||").unwrap();
+        let symbols = solve(&7290, &[6, 8, 6, 15], &operators)
+            .unwrap()
+            .iter()
+            .map(|operator| operator.symbol())
+            .collect::<Vec<&str>>();
+
+        assert_eq!(symbols, ["*", "||", "*"]);
+    }
+
+    // These can't be undone, the values before them are computed forwards.
+    #[test]
+    fn unknown_inverses() {
+        assert_eq!(solved(&[(0, &[5, 0])], "*"), [0]);
+        assert_eq!(solved(&[(0, &[5, 3, 0]), (1, &[5, 3, 0])], "+,*"), [0]);
+        assert_eq!(solved(&[(3, &[6, 7, 10]), (4, &[6, 7, 10])], "+,%"), [3]);
+        assert_eq!(solved(&[(4, &[9, 2, 5, 4])], "/,max"), [4]);
+        assert_eq!(solved(&[(1, &[2, 3, 0]), (2, &[2, 3, 0])], "+,^"), [1]);
+    }
+}